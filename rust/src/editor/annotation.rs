@@ -1,7 +1,9 @@
 use super::AnnotationType;
 use crate::prelude::ByteIdx;
 
-#[derive(Debug, Copy, Clone)]
+// struct_field_names: `annotation_type` reads better than `kind` at the call sites.
+#[allow(clippy::struct_field_names)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
 pub struct Annotation {
     pub annotation_type: AnnotationType,
     pub start_byte_idx: ByteIdx,
@@ -1,4 +1,4 @@
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum AnnotationType {
     Match,
     SelectedMatch,
@@ -10,4 +10,10 @@ pub enum AnnotationType {
     LifetimeSpecifier,
     Comment,
     String,
+    MatchingBracket,
+    Selection,
+    Warning,
+    Error,
+    Added,
+    Removed,
 }
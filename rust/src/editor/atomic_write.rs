@@ -0,0 +1,272 @@
+use std::ffi::OsString;
+use std::fs::{copy, metadata, remove_file, rename, set_permissions, File, Metadata, OpenOptions};
+use std::io::{Error, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+
+/// How a file has been written.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Written {
+    Atomically,
+    // overwritten in place for the given reason, so that a crash could have truncated it
+    InPlace(&'static str),
+}
+
+/// Replaces the file at `path` with `contents` without ever leaving it half-written:
+/// the contents go to a temporary file in the same directory, which is synced to disk
+/// and then renamed over the original. The permissions and the owner of the original are kept.
+/// Where that is not possible, e.g. for a file with other hard links, which a rename would
+/// separate from it, the file is overwritten in place instead, and the reason returned.
+/// If `backup` is set, the previous contents are kept as `path~`.
+pub fn write_atomically(path: &Path, contents: &[u8], backup: bool) -> Result<Written, Error> {
+    replace_file(path, contents, backup, false)
+}
+
+/// Like `write_atomically`, but the file can only be read and written by its owner,
+/// whatever the permissions of an existing file, and is never overwritten in place.
+/// Meant for copies of unsaved text.
+pub fn write_private(path: &Path, contents: &[u8]) -> Result<(), Error> {
+    replace_file(path, contents, false, true).map(|_| ())
+}
+
+fn replace_file(
+    path: &Path,
+    contents: &[u8],
+    backup: bool,
+    private: bool,
+) -> Result<Written, Error> {
+    // write through symlinks instead of replacing them
+    let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    let original = metadata(&path).ok().filter(|_| !private);
+    if let Some(reason) = original.as_ref().and_then(in_place_reason) {
+        return write_in_place(&path, contents, backup, reason);
+    }
+    let temp_path = temp_path_for(&path);
+    let mode = if private {
+        Some(0o600)
+    } else {
+        original.as_ref().and_then(mode_of)
+    };
+    if let Err(err) = write_temp_file(&temp_path, contents, mode) {
+        let _ = remove_file(&temp_path);
+        // the directory may not be writable while the file itself is
+        if err.kind() == ErrorKind::PermissionDenied && original.is_some() {
+            return write_in_place(&path, contents, backup, "the directory is not writable");
+        }
+        return Err(err);
+    }
+    if let Some(original) = &original {
+        match keep_attributes(&temp_path, original) {
+            Ok(true) => {}
+            Ok(false) => {
+                let _ = remove_file(&temp_path);
+                let reason = "the owner of the file could not be kept";
+                return write_in_place(&path, contents, backup, reason);
+            }
+            Err(err) => {
+                let _ = remove_file(&temp_path);
+                return Err(err);
+            }
+        }
+    }
+    let result = if backup && original.is_some() {
+        copy(&path, backup_path_for(&path)).map(|_| ())
+    } else {
+        Ok(())
+    }
+    .and_then(|()| rename(&temp_path, &path));
+    if result.is_err() {
+        let _ = remove_file(&temp_path);
+    }
+    result?;
+    sync_parent_dir(&path);
+    Ok(Written::Atomically)
+}
+
+/// Returns the path of the backup file, like `main.rs~` for `main.rs`.
+pub fn backup_path_for(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push("~");
+    PathBuf::from(name)
+}
+
+fn temp_path_for(path: &Path) -> PathBuf {
+    let mut name = OsString::from(".");
+    name.push(path.file_name().unwrap_or_default());
+    name.push(format!(".{}.tmp", std::process::id()));
+    path.with_file_name(name)
+}
+
+/// Returns why the original can't be replaced by renaming another file over it.
+#[cfg(unix)]
+fn in_place_reason(original: &Metadata) -> Option<&'static str> {
+    use std::os::unix::fs::MetadataExt;
+    (original.nlink() > 1).then_some("the file has other hard links")
+}
+
+#[cfg(not(unix))]
+fn in_place_reason(_original: &Metadata) -> Option<&'static str> {
+    None
+}
+
+#[cfg(unix)]
+#[allow(clippy::unnecessary_wraps)]
+fn mode_of(original: &Metadata) -> Option<u32> {
+    use std::os::unix::fs::PermissionsExt;
+    Some(original.permissions().mode())
+}
+
+#[cfg(not(unix))]
+fn mode_of(_original: &Metadata) -> Option<u32> {
+    None
+}
+
+/// Gives the temporary file the owner and permissions of the original,
+/// returning false if the owner could not be kept.
+fn keep_attributes(temp_path: &Path, original: &Metadata) -> Result<bool, Error> {
+    // changing the owner can clear permission bits, so it comes first
+    if !keep_owner(temp_path, original) {
+        return Ok(false);
+    }
+    set_permissions(temp_path, original.permissions())?;
+    Ok(true)
+}
+
+#[cfg(unix)]
+fn keep_owner(temp_path: &Path, original: &Metadata) -> bool {
+    use std::os::unix::fs::{chown, MetadataExt};
+    let Ok(temp) = metadata(temp_path) else {
+        return false;
+    };
+    if temp.uid() == original.uid() && temp.gid() == original.gid() {
+        return true;
+    }
+    chown(temp_path, Some(original.uid()), Some(original.gid())).is_ok()
+}
+
+#[cfg(not(unix))]
+fn keep_owner(_temp_path: &Path, _original: &Metadata) -> bool {
+    true
+}
+
+fn write_in_place(
+    path: &Path,
+    contents: &[u8],
+    backup: bool,
+    reason: &'static str,
+) -> Result<Written, Error> {
+    if backup {
+        copy(path, backup_path_for(path))?;
+    }
+    let mut file = OpenOptions::new().write(true).truncate(true).open(path)?;
+    file.write_all(contents)?;
+    file.sync_all()?;
+    Ok(Written::InPlace(reason))
+}
+
+/// Creates the temporary file with the given permissions, so that its contents
+/// are never readable by more users than the original.
+fn write_temp_file(temp_path: &Path, contents: &[u8], mode: Option<u32>) -> Result<(), Error> {
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    if let Some(mode) = mode {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(mode);
+    }
+    #[cfg(not(unix))]
+    let _ = mode;
+    // a leftover temporary file might have wider permissions
+    let _ = remove_file(temp_path);
+    let mut file = options.open(temp_path)?;
+    file.write_all(contents)?;
+    file.sync_all()
+}
+
+// make the rename itself durable; not all platforms can open directories
+fn sync_parent_dir(path: &Path) {
+    if let Some(parent) = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+    {
+        if let Ok(dir) = File::open(parent) {
+            let _ = dir.sync_all();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::{read_to_string, write};
+
+    #[test]
+    fn test_write_atomically() {
+        let dir = std::env::temp_dir().join(format!("hecto-atomic-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("file.txt");
+
+        assert_eq!(
+            write_atomically(&path, b"first", false).unwrap(),
+            Written::Atomically
+        );
+        assert_eq!(read_to_string(&path).unwrap(), "first");
+        assert!(!backup_path_for(&path).exists());
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            set_permissions(&path, std::fs::Permissions::from_mode(0o640)).unwrap();
+        }
+        write_atomically(&path, b"second", true).unwrap();
+        assert_eq!(read_to_string(&path).unwrap(), "second");
+        assert_eq!(read_to_string(backup_path_for(&path)).unwrap(), "first");
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o640);
+        }
+
+        // no temporary files are left behind
+        write(dir.join("other"), "").unwrap();
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 3);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_write_hard_linked_file_in_place() {
+        let dir = std::env::temp_dir().join(format!("hecto-linked-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("file.txt");
+        let link = dir.join("link.txt");
+        write(&path, "first").unwrap();
+        std::fs::hard_link(&path, &link).unwrap();
+
+        // renaming over the file would separate it from the link
+        assert_eq!(
+            write_atomically(&path, b"second", false).unwrap(),
+            Written::InPlace("the file has other hard links")
+        );
+        assert_eq!(read_to_string(&link).unwrap(), "second");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_write_keeps_owner() {
+        use std::os::unix::fs::{chown, MetadataExt};
+        let dir = std::env::temp_dir().join(format!("hecto-owner-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("file.txt");
+        write(&path, "first").unwrap();
+        // only possible with the privileges to give files away
+        if chown(&path, Some(4321), Some(4321)).is_ok() {
+            write_atomically(&path, b"second", false).unwrap();
+            let metadata = metadata(&path).unwrap();
+            assert_eq!((metadata.uid(), metadata.gid()), (4321, 4321));
+            assert_eq!(read_to_string(&path).unwrap(), "second");
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,112 @@
+use super::Settings;
+
+#[derive(Default, Debug)]
+pub struct CliArgs {
+    pub filename: Option<String>,
+    pub settings: Settings,
+    // write the buffer to stdout when quitting
+    pub to_stdout: bool,
+    // the file to record the events of the session to
+    pub record: Option<String>,
+    pub errors: Vec<String>,
+}
+
+impl CliArgs {
+    /// Parses the command line arguments, without the program name.
+    /// Supported options:
+    /// - `--tab-width=N`: render tabs up to the next multiple of N columns
+    /// - `--expand-tabs`: insert spaces instead of a tab character
+    /// - `--backup`: keep the previous contents as `file~` when saving
+    /// - `--readonly`: open the file for viewing only
+    /// - `--stdout`: write the buffer to stdout when quitting
+    /// - `--no-scroll-regions`: redraw instead of scrolling, for terminals which garble the screen
+    /// - `--no-mouse`: leave the mouse to the terminal, e.g. to copy text from it
+    /// - `--record=FILE`: record the keys pressed to FILE, to replay them in a test
+    ///
+    /// A filename of `-` reads the buffer from stdin.
+    ///
+    /// Unknown options are collected in `errors` instead of aborting,
+    /// so that the editor can still start and report them.
+    pub fn parse<I>(args: I) -> Self
+    where
+        I: IntoIterator<Item = String>,
+    {
+        let mut result = Self::default();
+        for arg in args {
+            if let Some(value) = arg.strip_prefix("--tab-width=") {
+                match value.parse::<usize>() {
+                    Ok(tab_width) if tab_width > 0 => result.settings.tab_width = tab_width,
+                    _ => result.errors.push(format!("Invalid tab width: {value}")),
+                }
+            } else if arg == "--expand-tabs" {
+                result.settings.expand_tabs = true;
+            } else if arg == "--backup" {
+                result.settings.backup = true;
+            } else if arg == "--readonly" {
+                result.settings.read_only = true;
+            } else if arg == "--stdout" {
+                result.to_stdout = true;
+            } else if let Some(path) = arg.strip_prefix("--record=") {
+                result.record = Some(String::from(path));
+            } else if arg == "--no-scroll-regions" {
+                result.settings.scroll_regions = false;
+            } else if arg == "--no-mouse" {
+                result.settings.mouse = false;
+            } else if arg.starts_with("--") {
+                result.errors.push(format!("Unknown option: {arg}"));
+            } else if result.filename.is_none() {
+                // only load the first file for now
+                result.filename = Some(arg);
+            }
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> CliArgs {
+        CliArgs::parse(args.iter().map(|arg| String::from(*arg)))
+    }
+
+    #[test]
+    fn test_parse() {
+        let args = parse(&[]);
+        assert_eq!(args.filename, None);
+        assert_eq!(args.settings, Settings::default());
+
+        let args = parse(&[
+            "--tab-width=8",
+            "main.rs",
+            "--expand-tabs",
+            "other.rs",
+            "--backup",
+            "--readonly",
+        ]);
+        assert_eq!(args.filename.as_deref(), Some("main.rs"));
+        assert_eq!(args.settings.tab_width, 8);
+        assert!(args.settings.expand_tabs);
+        assert!(args.settings.backup);
+        assert!(args.settings.read_only);
+        assert!(args.errors.is_empty());
+
+        let args = parse(&[
+            "-",
+            "--stdout",
+            "--no-scroll-regions",
+            "--no-mouse",
+            "--record=keys.log",
+        ]);
+        assert_eq!(args.filename.as_deref(), Some("-"));
+        assert!(args.to_stdout);
+        assert_eq!(args.record.as_deref(), Some("keys.log"));
+        assert!(!args.settings.scroll_regions);
+        assert!(!args.settings.mouse);
+
+        let args = parse(&["--tab-width=0", "--tab-width=x", "--unknown"]);
+        assert_eq!(args.settings.tab_width, Settings::default().tab_width);
+        assert_eq!(args.errors.len(), 3);
+    }
+}
@@ -1,5 +1,5 @@
 use crossterm::event::{
-    KeyCode::{Backspace, Char, Delete, Enter, Tab},
+    KeyCode::{BackTab, Backspace, Char, Delete, Enter, Tab},
     KeyEvent, KeyModifiers,
 };
 use std::convert::TryFrom;
@@ -8,6 +8,12 @@ use std::convert::TryFrom;
 pub enum Edit {
     Insert(char),
     InsertNewLine,
+    // start a new line below the current one, without splitting it
+    InsertLineBelow,
+    InsertTab,
+    Dedent,
+    ToggleComment,
+    ToggleLineEnding,
     DeleteBackward,
     Delete,
 }
@@ -25,7 +31,13 @@ impl TryFrom<KeyEvent> for Edit {
             }
             (Delete, KeyModifiers::NONE) | (Char('d'), KeyModifiers::CONTROL) => Ok(Self::Delete),
             (Enter, KeyModifiers::NONE) => Ok(Self::InsertNewLine),
-            (Tab, KeyModifiers::NONE) => Ok(Self::Insert('\t')),
+            // only reported with the enhanced keyboard protocol, see `Terminal::initialize`
+            (Enter, KeyModifiers::CONTROL) => Ok(Self::InsertLineBelow),
+            (Tab, KeyModifiers::NONE) => Ok(Self::InsertTab),
+            (BackTab, _) => Ok(Self::Dedent),
+            // most terminals send Ctrl-/ as Ctrl-_, which crossterm reports as Ctrl-7
+            (Char('/' | '7'), KeyModifiers::CONTROL) => Ok(Self::ToggleComment),
+            (Char('l'), KeyModifiers::CONTROL) => Ok(Self::ToggleLineEnding),
             _ => Err(format!(
                 "Unrecognized key: {code:?}, modifiers: {modifiers:?}"
             )),
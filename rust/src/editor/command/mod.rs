@@ -2,9 +2,11 @@ use crate::prelude::Size;
 use crossterm::event::Event::{self, Key};
 use std::convert::TryFrom;
 mod edit;
+mod mouse;
 mod movecommand;
 mod system;
 pub use edit::Edit;
+pub use mouse::Mouse;
 pub use movecommand::Move;
 pub use system::System;
 
@@ -13,6 +15,7 @@ pub enum Command {
     Move(Move),
     Edit(Edit),
     System(System),
+    Mouse(Mouse),
 }
 
 impl TryFrom<Event> for Command {
@@ -29,6 +32,7 @@ impl TryFrom<Event> for Command {
                 width: width16 as usize,
                 height: height16 as usize,
             }))),
+            Event::Mouse(mouse_event) => Mouse::try_from(mouse_event).map(Command::Mouse),
             _ => Err(format!("Unrecognized event: {event:?}")),
         }
     }
@@ -0,0 +1,33 @@
+use crate::prelude::Position;
+use crossterm::event::{MouseButton, MouseEvent, MouseEventKind};
+use std::convert::TryFrom;
+
+#[derive(Copy, Clone)]
+pub enum Mouse {
+    // the left button was pressed at the position
+    Click(Position),
+    // the mouse was moved to the position with the left button held down
+    Drag(Position),
+    ScrollUp,
+    ScrollDown,
+}
+
+impl TryFrom<MouseEvent> for Mouse {
+    type Error = String;
+    fn try_from(event: MouseEvent) -> Result<Self, Self::Error> {
+        let MouseEvent {
+            kind, column, row, ..
+        } = event;
+        let position = Position {
+            col: usize::from(column),
+            row: usize::from(row),
+        };
+        match kind {
+            MouseEventKind::Down(MouseButton::Left) => Ok(Self::Click(position)),
+            MouseEventKind::Drag(MouseButton::Left) => Ok(Self::Drag(position)),
+            MouseEventKind::ScrollUp => Ok(Self::ScrollUp),
+            MouseEventKind::ScrollDown => Ok(Self::ScrollDown),
+            _ => Err(format!("Unrecognized mouse event: {kind:?}")),
+        }
+    }
+}
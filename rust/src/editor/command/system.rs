@@ -5,13 +5,18 @@ use crossterm::event::{
 };
 use std::convert::TryFrom;
 
+const CONTROL_SHIFT: KeyModifiers = KeyModifiers::CONTROL.union(KeyModifiers::SHIFT);
+
 #[derive(Copy, Clone)]
 pub enum System {
     Resize(Size),
     Quit,
     Save,
+    SaveAs,
     Search,
     Dismiss,
+    ShowMessages,
+    Reload,
 }
 
 impl TryFrom<KeyEvent> for System {
@@ -23,7 +28,14 @@ impl TryFrom<KeyEvent> for System {
         match (code, modifiers) {
             (Char('q'), KeyModifiers::CONTROL) => Ok(Self::Quit),
             (Char('s'), KeyModifiers::CONTROL) => Ok(Self::Save),
+            // Ctrl-Shift-S needs the enhanced keyboard protocol, which reports the shift
+            // either as a modifier or, with alternate keys, as the uppercase letter
+            (Char('s' | 'S'), CONTROL_SHIFT) | (Char('S'), KeyModifiers::CONTROL) => {
+                Ok(Self::SaveAs)
+            }
             (Char('g'), KeyModifiers::CONTROL) => Ok(Self::Search),
+            (Char('o'), KeyModifiers::CONTROL) => Ok(Self::ShowMessages),
+            (Char('r'), KeyModifiers::CONTROL) => Ok(Self::Reload),
             (Esc, KeyModifiers::NONE) => Ok(Self::Dismiss),
             _ => Err(format!(
                 "Unrecognized key: {code:?}, modifiers: {modifiers:?}"
@@ -0,0 +1,90 @@
+use std::fs::{metadata, read};
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::io::{Error, ErrorKind};
+use std::path::Path;
+use std::time::SystemTime;
+
+/// A fingerprint of a file on disk, to notice when another program changed it.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct DiskState {
+    modified: Option<SystemTime>,
+    len: u64,
+    hash: u64,
+}
+
+impl DiskState {
+    /// Records the state of the file at `path`, which has just been read or written as `contents`.
+    pub fn of_contents(path: &Path, contents: &[u8]) -> Result<Self, Error> {
+        let metadata = metadata(path)?;
+        Ok(Self {
+            modified: metadata.modified().ok(),
+            len: metadata.len(),
+            hash: hash_of(contents),
+        })
+    }
+    /// Reads the current state of the file at `path`, or None if it does not exist (anymore).
+    pub fn read(path: &Path) -> Result<Option<Self>, Error> {
+        match read(path) {
+            Ok(contents) => Self::of_contents(path, &contents).map(Some),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+    /// Returns the current state of the file if it differs from this one.
+    /// The contents are only read and hashed if the metadata changed,
+    /// so that merely touching the file does not count as a change.
+    /// The new metadata of unchanged contents is taken over, so they are not hashed again.
+    pub fn changed_state(&mut self, path: &Path) -> Option<Self> {
+        let current = metadata(path).ok();
+        let same_metadata = current.as_ref().is_some_and(|metadata| {
+            metadata.len() == self.len && metadata.modified().ok() == self.modified
+        });
+        if same_metadata {
+            return None;
+        }
+        match Self::read(path) {
+            Ok(Some(state)) if state.hash == self.hash && state.len == self.len => {
+                self.modified = state.modified;
+                None
+            }
+            Ok(Some(state)) => Some(state),
+            // deleted or unreadable files are left for saving to deal with
+            Ok(None) | Err(_) => None,
+        }
+    }
+}
+
+fn hash_of(contents: &[u8]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    contents.hash(&mut hasher);
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::write;
+
+    #[test]
+    fn test_changed_state() {
+        let path = std::env::temp_dir().join(format!("hecto-disk-{}.txt", std::process::id()));
+        write(&path, "first").unwrap();
+        let mut state = DiskState::read(&path).unwrap().unwrap();
+        assert_eq!(state.changed_state(&path), None);
+
+        // same contents, but touched
+        let file = std::fs::File::options().write(true).open(&path).unwrap();
+        file.set_modified(SystemTime::UNIX_EPOCH).unwrap();
+        assert_eq!(state.changed_state(&path), None);
+        // the new modification time is remembered
+        assert_eq!(state, DiskState::read(&path).unwrap().unwrap());
+
+        write(&path, "second").unwrap();
+        let mut changed = state.changed_state(&path).unwrap();
+        assert_eq!(changed, DiskState::read(&path).unwrap().unwrap());
+        assert_eq!(changed.changed_state(&path), None);
+
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(DiskState::read(&path).unwrap(), None);
+    }
+}
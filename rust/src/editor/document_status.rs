@@ -1,6 +1,8 @@
-use super::FileType;
+use super::{FileType, LineEnding, TextEncoding};
 use crate::prelude::LineIdx;
 
+// plain flags shown in the status bar, not a state machine
+#[allow(clippy::struct_excessive_bools)]
 #[derive(Default, Eq, PartialEq)]
 pub struct DocumentStatus {
     pub filename: Option<String>,
@@ -8,6 +10,12 @@ pub struct DocumentStatus {
     pub total_lines: usize,
     pub current_line_idx: LineIdx,
     pub modified: bool,
+    pub read_only: bool,
+    pub encoding: TextEncoding,
+    pub line_ending: LineEnding,
+    pub final_newline: bool,
+    // shown as a hex dump, without encoding and line endings
+    pub binary: bool,
 }
 impl DocumentStatus {
     pub fn filename_string(&self) -> String {
@@ -22,8 +30,26 @@ impl DocumentStatus {
             String::default()
         }
     }
+    pub fn read_only_string(&self) -> String {
+        if self.read_only {
+            String::from(" [RO]")
+        } else {
+            String::default()
+        }
+    }
     pub fn total_lines_string(&self) -> String {
-        format!("{} lines", self.total_lines)
+        if self.binary {
+            format!("{} rows", self.total_lines)
+        } else {
+            format!("{} lines", self.total_lines)
+        }
+    }
+    pub fn line_ending_string(&self) -> String {
+        if self.final_newline {
+            format!("{}", self.line_ending)
+        } else {
+            format!("{} noeol", self.line_ending)
+        }
     }
     pub fn position_string(&self) -> String {
         format!("{}/{}", self.current_line_idx, self.total_lines)
@@ -0,0 +1,265 @@
+use crate::prelude::Size;
+use crossterm::event::{
+    Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers, MouseButton, MouseEvent, MouseEventKind,
+};
+use std::fs::File;
+use std::io::{BufWriter, Error, Write};
+
+// The keys without a character, by the name they are logged with.
+const NAMED_KEYS: [(&str, KeyCode); 14] = [
+    ("Backspace", KeyCode::Backspace),
+    ("Enter", KeyCode::Enter),
+    ("Left", KeyCode::Left),
+    ("Right", KeyCode::Right),
+    ("Up", KeyCode::Up),
+    ("Down", KeyCode::Down),
+    ("Home", KeyCode::Home),
+    ("End", KeyCode::End),
+    ("PageUp", KeyCode::PageUp),
+    ("PageDown", KeyCode::PageDown),
+    ("Tab", KeyCode::Tab),
+    ("BackTab", KeyCode::BackTab),
+    ("Delete", KeyCode::Delete),
+    ("Esc", KeyCode::Esc),
+];
+
+// The mouse events worth replaying; moving the mouse and the other buttons are left out.
+const MOUSE_KINDS: [(&str, MouseEventKind); 5] = [
+    ("press", MouseEventKind::Down(MouseButton::Left)),
+    ("release", MouseEventKind::Up(MouseButton::Left)),
+    ("drag", MouseEventKind::Drag(MouseButton::Left)),
+    ("scroll-up", MouseEventKind::ScrollUp),
+    ("scroll-down", MouseEventKind::ScrollDown),
+];
+
+const MODIFIERS: [(&str, KeyModifiers); 6] = [
+    ("ctrl", KeyModifiers::CONTROL),
+    ("alt", KeyModifiers::ALT),
+    ("shift", KeyModifiers::SHIFT),
+    // only reported with the enhanced keyboard protocol
+    ("super", KeyModifiers::SUPER),
+    ("hyper", KeyModifiers::HYPER),
+    ("meta", KeyModifiers::META),
+];
+
+/// Writes the events the editor processes to a file, one per line, to replay the session later.
+/// The file starts with the size of the terminal, and lines starting with `#` are comments:
+///
+/// ```text
+/// resize 80 24
+/// key - char:a
+/// key ctrl char:s
+/// key alt+shift PageDown
+/// mouse - press 10 4
+/// ```
+pub struct EventRecorder {
+    file: BufWriter<File>,
+}
+
+impl EventRecorder {
+    pub fn create(path: &str, size: Size) -> Result<Self, Error> {
+        let mut recorder = Self {
+            file: BufWriter::new(File::create(path)?),
+        };
+        recorder.record(&resize_event(size))?;
+        Ok(recorder)
+    }
+    /// Appends the event, flushing right away so that the log survives a crash.
+    /// Events which do nothing, like releasing a key, are skipped.
+    pub fn record(&mut self, event: &Event) -> Result<(), Error> {
+        if let Some(line) = format_event(event) {
+            writeln!(self.file, "{line}")?;
+            self.file.flush()?;
+        }
+        Ok(())
+    }
+}
+
+#[allow(clippy::as_conversions, clippy::cast_possible_truncation)]
+fn resize_event(size: Size) -> Event {
+    Event::Resize(size.width as u16, size.height as u16)
+}
+
+fn format_event(event: &Event) -> Option<String> {
+    match event {
+        Event::Key(KeyEvent {
+            code,
+            modifiers,
+            // a repeated key acts like a press, and is replayed as one
+            kind: KeyEventKind::Press | KeyEventKind::Repeat,
+            ..
+        }) => {
+            let code = match code {
+                KeyCode::Char(c) => format!("char:{c}"),
+                KeyCode::F(n) => format!("F{n}"),
+                code => String::from(NAMED_KEYS.iter().find(|(_, key)| key == code)?.0),
+            };
+            Some(format!("key {} {code}", format_modifiers(*modifiers)))
+        }
+        Event::Mouse(MouseEvent {
+            kind,
+            column,
+            row,
+            modifiers,
+        }) => {
+            let (name, _) = MOUSE_KINDS
+                .iter()
+                .find(|(_, mouse_kind)| mouse_kind == kind)?;
+            let modifiers = format_modifiers(*modifiers);
+            Some(format!("mouse {modifiers} {name} {column} {row}"))
+        }
+        Event::Resize(width, height) => Some(format!("resize {width} {height}")),
+        _ => None,
+    }
+}
+
+fn format_modifiers(modifiers: KeyModifiers) -> String {
+    let names: Vec<&str> = MODIFIERS
+        .iter()
+        .filter(|(_, modifier)| modifiers.contains(*modifier))
+        .map(|(name, _)| *name)
+        .collect();
+    if names.is_empty() {
+        String::from("-")
+    } else {
+        names.join("+")
+    }
+}
+
+#[cfg(test)]
+fn parse_modifiers(names: &str) -> Option<KeyModifiers> {
+    if names == "-" {
+        return Some(KeyModifiers::NONE);
+    }
+    names
+        .split('+')
+        .try_fold(KeyModifiers::NONE, |modifiers, name| {
+            let (_, modifier) = MODIFIERS
+                .iter()
+                .find(|(modifier_name, _)| *modifier_name == name)?;
+            Some(modifiers | *modifier)
+        })
+}
+
+#[cfg(test)]
+fn parse_event(line: &str) -> Result<Event, String> {
+    let invalid = || format!("Invalid event: {line}");
+    let mut fields = line.splitn(3, ' ');
+    match (fields.next(), fields.next(), fields.next()) {
+        (Some("resize"), Some(width), Some(height)) => {
+            let width = width.parse().map_err(|_| invalid())?;
+            let height = height.parse().map_err(|_| invalid())?;
+            Ok(Event::Resize(width, height))
+        }
+        (Some("mouse"), Some(modifiers), Some(rest)) => {
+            let modifiers = parse_modifiers(modifiers).ok_or_else(invalid)?;
+            let mut fields = rest.split(' ');
+            let (Some(name), Some(column), Some(row), None) =
+                (fields.next(), fields.next(), fields.next(), fields.next())
+            else {
+                return Err(invalid());
+            };
+            let (_, kind) = MOUSE_KINDS
+                .iter()
+                .find(|(kind_name, _)| *kind_name == name)
+                .ok_or_else(invalid)?;
+            Ok(Event::Mouse(MouseEvent {
+                kind: *kind,
+                column: column.parse().map_err(|_| invalid())?,
+                row: row.parse().map_err(|_| invalid())?,
+                modifiers,
+            }))
+        }
+        (Some("key"), Some(modifiers), Some(code)) => {
+            let key_modifiers = parse_modifiers(modifiers).ok_or_else(invalid)?;
+            let key_code = if let Some(c) = code.strip_prefix("char:") {
+                let mut chars = c.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => KeyCode::Char(c),
+                    _ => return Err(invalid()),
+                }
+            } else if let Some(n) = code.strip_prefix('F').and_then(|n| n.parse().ok()) {
+                KeyCode::F(n)
+            } else {
+                NAMED_KEYS
+                    .iter()
+                    .find(|(name, _)| *name == code)
+                    .ok_or_else(invalid)?
+                    .1
+            };
+            Ok(Event::Key(KeyEvent::new(key_code, key_modifiers)))
+        }
+        _ => Err(invalid()),
+    }
+}
+
+/// Reads the events of a log written by `EventRecorder`.
+#[cfg(test)]
+pub fn parse_events(log: &str) -> Result<Vec<Event>, String> {
+    log.lines()
+        .filter(|line| !line.trim().is_empty() && !line.starts_with('#'))
+        .map(parse_event)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_and_parse() {
+        let events = [
+            Event::Resize(80, 24),
+            Event::Key(KeyEvent::new(KeyCode::Char(' '), KeyModifiers::NONE)),
+            Event::Key(KeyEvent::new(KeyCode::Char('s'), KeyModifiers::CONTROL)),
+            Event::Key(KeyEvent::new(
+                KeyCode::PageDown,
+                KeyModifiers::SHIFT | KeyModifiers::ALT,
+            )),
+            Event::Key(KeyEvent::new(KeyCode::F(5), KeyModifiers::NONE)),
+            Event::Mouse(MouseEvent {
+                kind: MouseEventKind::Drag(MouseButton::Left),
+                column: 3,
+                row: 7,
+                modifiers: KeyModifiers::CONTROL,
+            }),
+        ];
+        let log: Vec<String> = events.iter().filter_map(format_event).collect();
+        assert_eq!(
+            log,
+            [
+                "resize 80 24",
+                "key - char: ",
+                "key ctrl char:s",
+                "key alt+shift PageDown",
+                "key - F5",
+                "mouse ctrl drag 3 7"
+            ]
+        );
+        let log = format!("# a comment\n\n{}\n", log.join("\n"));
+        assert_eq!(parse_events(&log).unwrap(), events);
+        assert!(parse_event("key - char:ab").is_err());
+        assert!(parse_event("key fn Enter").is_err());
+        let released = Event::Key(KeyEvent::new_with_kind(
+            KeyCode::Enter,
+            KeyModifiers::CONTROL | KeyModifiers::SUPER,
+            KeyEventKind::Release,
+        ));
+        assert_eq!(format_event(&released), None);
+        let repeated = Event::Key(KeyEvent::new_with_kind(
+            KeyCode::Enter,
+            KeyModifiers::CONTROL | KeyModifiers::SUPER,
+            KeyEventKind::Repeat,
+        ));
+        assert_eq!(format_event(&repeated).unwrap(), "key ctrl+super Enter");
+        assert!(parse_event("mouse - press 1").is_err());
+        assert!(parse_event("mouse - moved 1 2").is_err());
+        let moved = Event::Mouse(MouseEvent {
+            kind: MouseEventKind::Moved,
+            column: 1,
+            row: 2,
+            modifiers: KeyModifiers::NONE,
+        });
+        assert_eq!(format_event(&moved), None);
+    }
+}
@@ -1,4 +1,4 @@
-use super::FileType;
+use super::{DiskState, FileType, TextEncoding};
 use std::{
     fmt,
     path::{Path, PathBuf},
@@ -8,6 +8,11 @@ use std::{
 pub struct FileInfo {
     path: Option<PathBuf>,
     file_type: Option<FileType>,
+    // the state of the file when it was last read or written
+    disk_state: Option<DiskState>,
+    // a change on disk whose prompt has been dismissed, so that it is not asked about again
+    dismissed_disk_state: Option<DiskState>,
+    encoding: TextEncoding,
 }
 
 impl FileInfo {
@@ -19,7 +24,13 @@ impl FileInfo {
             .and_then(|path| path.extension())
             .and_then(|ext| ext.to_str())
             .and_then(FileType::from);
-        Self { path, file_type }
+        Self {
+            path,
+            file_type,
+            disk_state: None,
+            dismissed_disk_state: None,
+            encoding: TextEncoding::default(),
+        }
     }
     pub fn get_path(&self) -> Option<&Path> {
         self.path.as_deref()
@@ -30,6 +41,41 @@ impl FileInfo {
     pub fn get_file_type(&self) -> Option<FileType> {
         self.file_type
     }
+    pub fn get_encoding(&self) -> TextEncoding {
+        self.encoding
+    }
+    pub fn set_encoding(&mut self, encoding: TextEncoding) {
+        self.encoding = encoding;
+    }
+    pub fn set_disk_state(&mut self, disk_state: Option<DiskState>) {
+        self.disk_state = disk_state;
+    }
+    /// Returns the current state of the file on disk
+    /// if another program changed it since it was last read or written.
+    pub fn changed_disk_state(&mut self) -> Option<DiskState> {
+        let path = self.path.as_deref()?;
+        self.disk_state.as_mut()?.changed_state(path)
+    }
+    /// Stops reporting the current change on disk, without accepting it as read.
+    /// Saving still asks about it, and a further change is reported again.
+    pub fn dismiss_changed_disk_state(&mut self) {
+        self.dismissed_disk_state = self.changed_disk_state();
+    }
+    /// Whether the file changed on disk in a way that has not been dismissed.
+    pub fn has_undismissed_change(&mut self) -> bool {
+        let Some(path) = self.path.as_deref() else {
+            return false;
+        };
+        // while the file stays as dismissed, this only compares the metadata
+        if self
+            .dismissed_disk_state
+            .as_mut()
+            .is_some_and(|dismissed| dismissed.changed_state(path).is_none())
+        {
+            return false;
+        }
+        self.changed_disk_state().is_some()
+    }
 }
 
 impl fmt::Display for FileInfo {
@@ -39,7 +85,7 @@ impl fmt::Display for FileInfo {
             .and_then(|path| path.file_name())
             .and_then(|name| name.to_str())
             .unwrap_or("[No Name]");
-        write!(formatter, "{name}",)
+        write!(formatter, "{name}")
     }
 }
 
@@ -57,6 +103,10 @@ mod tests {
             FileInfo::from("sample.rs").get_file_type().unwrap(),
             FileType::Rust
         );
+        assert_eq!(
+            FileInfo::from("Cargo.toml").get_file_type().unwrap(),
+            FileType::Toml
+        );
         assert_eq!(FileInfo::from("sample.unknown").get_file_type(), None);
     }
 }
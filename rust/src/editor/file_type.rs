@@ -1,16 +1,146 @@
 #[derive(Default, Eq, PartialEq, Debug, Copy, Clone)]
 pub enum FileType {
     Rust,
+    Shell,
+    Toml,
     #[default]
     Text,
 }
 
+const RUST_AUTO_PAIRS: [(char, char); 5] =
+    [('(', ')'), ('[', ']'), ('{', '}'), ('"', '"'), ('\'', '\'')];
+
 impl FileType {
     pub fn from(ext: &str) -> Option<Self> {
         match ext {
             "txt" => Some(FileType::Text),
             "rs" => Some(FileType::Rust),
+            "sh" | "bash" | "zsh" => Some(FileType::Shell),
+            "toml" => Some(FileType::Toml),
             _ => None,
         }
     }
+    /// The token which starts a comment running until the end of the line.
+    pub fn line_comment(self) -> Option<&'static str> {
+        match self {
+            Self::Rust => Some("//"),
+            Self::Shell | Self::Toml => Some("#"),
+            Self::Text => None,
+        }
+    }
+    /// The (open, close) pairs which are completed automatically while typing.
+    pub fn auto_pairs(self) -> &'static [(char, char)] {
+        match self {
+            Self::Rust => &RUST_AUTO_PAIRS,
+            Self::Shell | Self::Toml | Self::Text => &[],
+        }
+    }
+    pub fn is_auto_pair(self, open: char, close: char) -> bool {
+        self.auto_pairs().contains(&(open, close))
+    }
+    pub fn is_auto_pair_close(self, close: char) -> bool {
+        self.auto_pairs().iter().any(|&(_, c)| c == close)
+    }
+    /// Returns the closing partner of `open`, if it should be inserted automatically
+    /// when `open` is typed after the text `before` on the line and in front of `next`.
+    pub fn closing_pair(self, open: char, before: &str, next: Option<char>) -> Option<char> {
+        let close = self
+            .auto_pairs()
+            .iter()
+            .find_map(|&(o, c)| (o == open).then_some(c))?;
+        // don't pair in front of a word, like `(|foo`
+        if next.is_some_and(|c| !c.is_whitespace() && !self.is_auto_pair_close(c)) {
+            return None;
+        }
+        // quotes right after a word are most likely an apostrophe or a string prefix end
+        if open == close && before.chars().next_back().is_some_and(is_word_char) {
+            return None;
+        }
+        match self {
+            Self::Rust if open == '\'' => (!starts_lifetime(before)).then_some(close),
+            _ => Some(close),
+        }
+    }
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// Whether a quote typed after `before` starts a lifetime or a label rather than a char literal:
+/// after `&`, `<`, `+` or `:` as in `&'a`, `T: 'a + 'b`, at the start of a line or after
+/// `break` and `continue` as in `'outer: loop`, and anywhere inside generics like `<'a, 'b>`.
+fn starts_lifetime(before: &str) -> bool {
+    let trimmed = before.trim_end();
+    let last_word = trimmed.rsplit(|c| !is_word_char(c)).next();
+    trimmed.is_empty()
+        || trimmed.ends_with(['&', '<', '+', ':'])
+        || matches!(last_word, Some("break" | "continue"))
+        || generics_depth(before) > 0
+}
+
+/// How many generics are open at the end of `before`, like `Foo<` and `::<`.
+/// A `<` after a space is taken for a comparison, and `->` and `=>` close nothing.
+fn generics_depth(before: &str) -> usize {
+    let mut depth = 0_usize;
+    let mut prev = None;
+    for c in before.chars() {
+        match c {
+            '<' if prev.is_some_and(|p| is_word_char(p) || p == ':') => {
+                depth = depth.saturating_add(1);
+            }
+            '>' if !matches!(prev, Some('-' | '=')) => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+        prev = Some(c);
+    }
+    depth
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_closing_pair() {
+        let rust = FileType::Rust;
+        assert_eq!(rust.closing_pair('(', "", None), Some(')'));
+        assert_eq!(rust.closing_pair('{', "if x ", Some(')')), Some('}'));
+        assert_eq!(rust.closing_pair('(', "f ", Some('f')), None);
+        assert_eq!(rust.closing_pair('"', "let s =", None), Some('"'));
+        assert_eq!(rust.closing_pair('x', "", None), None);
+        assert_eq!(FileType::Text.closing_pair('(', "", None), None);
+    }
+
+    #[test]
+    fn test_closing_pair_char_literals() {
+        let rust = FileType::Rust;
+        assert_eq!(rust.closing_pair('\'', "let c = ", None), Some('\''));
+        assert_eq!(rust.closing_pair('\'', "let c =", None), Some('\''));
+        assert_eq!(rust.closing_pair('\'', "let a = ['x', ", None), Some('\''));
+        assert_eq!(rust.closing_pair('\'', "f(", None), Some('\''));
+        assert_eq!(rust.closing_pair('\'', "v[", Some(']')), Some('\''));
+        assert_eq!(rust.closing_pair('\'', "    'a' | ", None), Some('\''));
+        assert_eq!(
+            rust.closing_pair('\'', "x -> Option<char> { Some(", None),
+            Some('\'')
+        );
+        assert_eq!(rust.closing_pair('\'', "'a' => ", None), Some('\''));
+        // an apostrophe
+        assert_eq!(rust.closing_pair('\'', "// don", None), None);
+    }
+
+    #[test]
+    fn test_closing_pair_lifetimes() {
+        let rust = FileType::Rust;
+        assert_eq!(rust.closing_pair('\'', "fn f(s: &", None), None);
+        assert_eq!(rust.closing_pair('\'', "struct S<", None), None);
+        assert_eq!(rust.closing_pair('\'', "struct S<'a, ", None), None);
+        assert_eq!(rust.closing_pair('\'', "fn f<'a, 'b: ", None), None);
+        assert_eq!(rust.closing_pair('\'', "fn f<T: Fn() -> u8 + ", None), None);
+        assert_eq!(rust.closing_pair('\'', "f::<", None), None);
+        assert_eq!(rust.closing_pair('\'', "where T: ", None), None);
+        assert_eq!(rust.closing_pair('\'', "    ", None), None);
+        assert_eq!(rust.closing_pair('\'', "    break ", None), None);
+    }
 }
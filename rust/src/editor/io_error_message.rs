@@ -0,0 +1,54 @@
+use std::io::{Error, ErrorKind};
+use std::path::Path;
+
+/// Describes why saving to `path` failed, in words a user can act on.
+pub fn describe_save_error(err: &Error, path: &Path) -> String {
+    let path = path.display();
+    let reason = match err.kind() {
+        ErrorKind::PermissionDenied => "permission denied",
+        ErrorKind::NotFound => "directory does not exist",
+        ErrorKind::StorageFull => "disk is full",
+        ErrorKind::ReadOnlyFilesystem => "read-only file system",
+        ErrorKind::IsADirectory => "is a directory",
+        _ => return format!("Could not save {path}: {err}"),
+    };
+    format!("Could not save {path}: {reason}")
+}
+
+/// Returns the parent directory of `path` if it does not exist yet.
+pub fn missing_parent_dir(path: &Path) -> Option<&Path> {
+    path.parent()
+        .filter(|parent| !parent.as_os_str().is_empty() && !parent.exists())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_describe_save_error() {
+        let path = Path::new("dir/file.txt");
+        assert_eq!(
+            describe_save_error(&Error::from(ErrorKind::PermissionDenied), path),
+            "Could not save dir/file.txt: permission denied"
+        );
+        assert_eq!(
+            describe_save_error(&Error::from(ErrorKind::StorageFull), path),
+            "Could not save dir/file.txt: disk is full"
+        );
+        assert_eq!(
+            describe_save_error(&Error::other("oops"), path),
+            "Could not save dir/file.txt: oops"
+        );
+    }
+
+    #[test]
+    fn test_missing_parent_dir() {
+        assert_eq!(missing_parent_dir(Path::new("file.txt")), None);
+        assert_eq!(missing_parent_dir(Path::new("src/main.rs")), None);
+        assert_eq!(
+            missing_parent_dir(Path::new("no/such/dir/file.txt")),
+            Some(Path::new("no/such/dir"))
+        );
+    }
+}
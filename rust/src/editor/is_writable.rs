@@ -0,0 +1,52 @@
+use std::fs::metadata;
+use std::io::ErrorKind;
+use std::path::Path;
+
+/// Whether the file at `path` may be written, or created if it does not exist yet.
+/// Only the permissions are checked: opening the file for writing would notify
+/// file watchers and could block on special files like FIFOs.
+pub fn is_writable(path: &Path) -> bool {
+    match metadata(path) {
+        Ok(metadata) => !metadata.permissions().readonly() && has_write_access(path),
+        Err(err) => err.kind() == ErrorKind::NotFound,
+    }
+}
+
+/// Asks the system whether this process may write the file, taking its owner,
+/// group and ACLs into account, which the permission bits alone don't tell.
+#[cfg(unix)]
+fn has_write_access(path: &Path) -> bool {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+    let Ok(path) = CString::new(path.as_os_str().as_bytes()) else {
+        return false;
+    };
+    // SAFETY: `path` is a NUL-terminated string which lives until after the call.
+    unsafe { libc::access(path.as_ptr(), libc::W_OK) == 0 }
+}
+
+#[cfg(not(unix))]
+fn has_write_access(_path: &Path) -> bool {
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_writable() {
+        let dir = std::env::temp_dir().join(format!("hecto-writable-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("file.txt");
+        // a new file can be created
+        assert!(is_writable(&path));
+        std::fs::write(&path, "x").unwrap();
+        assert!(is_writable(&path));
+        let mut permissions = metadata(&path).unwrap().permissions();
+        permissions.set_readonly(true);
+        std::fs::set_permissions(&path, permissions).unwrap();
+        assert!(!is_writable(&path));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
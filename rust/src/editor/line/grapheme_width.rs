@@ -1,7 +1,9 @@
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub enum GraphemeWidth {
     Half,
     Full,
+    // a tab spans until the next tab stop, so its width depends on its column
+    Tab(usize),
 }
 
 impl GraphemeWidth {
@@ -12,16 +14,14 @@ impl GraphemeWidth {
         match self {
             Self::Half => 1,
             Self::Full => 2,
+            Self::Tab(width) => width,
         }
     }
 }
 
 impl From<GraphemeWidth> for usize {
     fn from(val: GraphemeWidth) -> Self {
-        match val {
-            GraphemeWidth::Half => 1,
-            GraphemeWidth::Full => 2,
-        }
+        val.as_usize()
     }
 }
 
@@ -41,7 +41,9 @@ mod tests {
     fn test_as_usize() {
         let half = GraphemeWidth::Half;
         let full = GraphemeWidth::Full;
+        let tab = GraphemeWidth::Tab(3);
         assert_eq!(half.as_usize(), 1);
         assert_eq!(full.as_usize(), 2);
+        assert_eq!(tab.as_usize(), 3);
     }
 }
@@ -1,4 +1,4 @@
-use crate::editor::{AnnotatedString, Annotation};
+use crate::editor::{settings::DEFAULT_TAB_WIDTH, AnnotatedString, Annotation};
 use crate::prelude::{ByteIdx, ColIdx, GraphemeIdx};
 use grapheme_width::GraphemeWidth;
 use std::cmp::min;
@@ -9,28 +9,117 @@ mod grapheme_width;
 mod text_fragment;
 use text_fragment::TextFragment;
 
-#[derive(Default, Clone)]
+#[derive(Clone)]
 pub struct Line {
     fragments: Vec<TextFragment>,
     pub string: String,
+    tab_width: usize,
+}
+
+impl Default for Line {
+    fn default() -> Self {
+        Self::with_tab_width("", DEFAULT_TAB_WIDTH)
+    }
 }
 
 impl Line {
     pub fn from(string: &str) -> Self {
+        Self::with_tab_width(string, DEFAULT_TAB_WIDTH)
+    }
+    pub fn with_tab_width(string: &str, tab_width: usize) -> Self {
         debug_assert!(string.is_empty() || string.lines().count() == 1);
+        let tab_width = tab_width.max(1);
         Self {
-            fragments: Self::string_to_fragments(string),
+            fragments: Self::string_to_fragments(string, tab_width),
             string: String::from(string),
+            tab_width,
         }
     }
-    fn string_to_fragments(string: &str) -> Vec<TextFragment> {
-        string
+    fn string_to_fragments(string: &str, tab_width: usize) -> Vec<TextFragment> {
+        let mut fragments: Vec<TextFragment> = string
             .grapheme_indices(true)
             .map(|(start_byte_idx, grapheme)| TextFragment::new(start_byte_idx, grapheme))
-            .collect()
+            .collect();
+        Self::update_tab_widths(&mut fragments, tab_width);
+        fragments
+    }
+    /// Expands every tab to the next tab stop.
+    /// This has to run whenever the columns of the fragments may have changed.
+    fn update_tab_widths(fragments: &mut [TextFragment], tab_width: usize) {
+        let mut col: ColIdx = 0;
+        for fragment in fragments {
+            if fragment.grapheme == "\t" {
+                let offset = col.checked_rem(tab_width).unwrap_or(0);
+                fragment.width = GraphemeWidth::Tab(tab_width.saturating_sub(offset));
+            }
+            col = fragment.width.saturating_add(col);
+        }
+    }
+    /// Replaces the bytes in `range`, which has to start and end at grapheme boundaries,
+    /// with `replacement`. Only the graphemes around the change are segmented again,
+    /// until the boundaries agree with the old ones; the fragments after that are shifted.
+    fn replace_range(&mut self, range: Range<ByteIdx>, replacement: &str) {
+        // the new text may merge with the grapheme before it, e.g. a combining accent
+        let first_idx = self
+            .fragments
+            .partition_point(|fragment| fragment.start_byte_idx < range.start)
+            .saturating_sub(1);
+        let window_start = self
+            .fragments
+            .get(first_idx)
+            .map_or(0, |fragment| fragment.start_byte_idx);
+        let mut old_idx = self
+            .fragments
+            .partition_point(|fragment| fragment.start_byte_idx < range.end);
+        let new_end = range.start.saturating_add(replacement.len());
+        let shift = |start_byte_idx: ByteIdx| {
+            start_byte_idx
+                .saturating_sub(range.end)
+                .saturating_add(new_end)
+        };
+        self.string.replace_range(range.clone(), replacement);
+
+        let mut new_fragments = Vec::new();
+        let mut converged = false;
+        for (offset, grapheme) in self.string[window_start..].grapheme_indices(true) {
+            let start_byte_idx = window_start.saturating_add(offset);
+            if start_byte_idx >= new_end {
+                while self
+                    .fragments
+                    .get(old_idx)
+                    .is_some_and(|fragment| shift(fragment.start_byte_idx) < start_byte_idx)
+                {
+                    old_idx = old_idx.saturating_add(1);
+                }
+                converged = self
+                    .fragments
+                    .get(old_idx)
+                    .is_some_and(|fragment| shift(fragment.start_byte_idx) == start_byte_idx);
+                if converged {
+                    break;
+                }
+            }
+            new_fragments.push(TextFragment::new(start_byte_idx, grapheme));
+        }
+        let tail: Vec<TextFragment> = if converged {
+            self.fragments
+                .drain(old_idx..)
+                .map(|mut fragment| {
+                    fragment.start_byte_idx = shift(fragment.start_byte_idx);
+                    fragment
+                })
+                .collect()
+        } else {
+            Vec::new()
+        };
+        self.fragments.truncate(first_idx);
+        self.fragments.extend(new_fragments);
+        self.fragments.extend(tail);
+        Self::update_tab_widths(&mut self.fragments, self.tab_width);
     }
-    fn rebuild_fragments(&mut self) {
-        self.fragments = Self::string_to_fragments(&self.string);
+    pub fn set_tab_width(&mut self, tab_width: usize) {
+        self.tab_width = tab_width.max(1);
+        Self::update_tab_widths(&mut self.fragments, self.tab_width);
     }
     #[allow(dead_code)]
     pub fn get_visible_graphemes(&self, range: Range<ColIdx>) -> String {
@@ -42,7 +131,7 @@ impl Line {
     /// Parameters:
     /// - range: the range of columns to get the annotated string from.
     /// - query: the query to highlight in the annotated string.
-    /// - selected_match: the selected match to highlight in the annotated string. this is
+    /// - `selected_match`: the selected match to highlight in the annotated string. this is
     ///   only applied if the query is not empty.
     pub fn get_annotated_visible_substr(
         &self,
@@ -119,13 +208,24 @@ impl Line {
                 if let Some(replacement) = fragment.replacement {
                     let start_byte_idx = fragment.start_byte_idx;
                     let end_byte_idx = start_byte_idx.saturating_add(fragment.grapheme.len());
-                    result.replace(start_byte_idx, end_byte_idx, &replacement.to_string());
+                    // pad the replacement, so that tabs fill up to the next tab stop
+                    let width = fragment.width.as_usize();
+                    result.replace(
+                        start_byte_idx,
+                        end_byte_idx,
+                        &format!("{replacement:<width$}"),
+                    );
                 }
             }
         }
 
         result
     }
+    pub fn get_grapheme(&self, grapheme_idx: GraphemeIdx) -> Option<&str> {
+        self.fragments
+            .get(grapheme_idx)
+            .map(|fragment| fragment.grapheme.as_str())
+    }
     pub fn grapheme_count(&self) -> GraphemeIdx {
         self.fragments.len()
     }
@@ -136,44 +236,70 @@ impl Line {
             .map(|fragment| fragment.width.as_usize())
             .sum()
     }
+    /// Returns the index of the grapheme covering the given column,
+    /// or the grapheme count if the column is beyond the end of the line.
+    pub fn grapheme_idx_at(&self, col: ColIdx) -> GraphemeIdx {
+        let mut end: ColIdx = 0;
+        self.fragments
+            .iter()
+            .position(|fragment| {
+                end = end.saturating_add(fragment.width.as_usize());
+                end > col
+            })
+            .unwrap_or(self.grapheme_count())
+    }
     pub fn width(&self) -> ColIdx {
         self.width_until(self.grapheme_count())
     }
 
-    pub fn insert(&mut self, at: GraphemeIdx, string: &str) {
+    /// Inserts `string` before the grapheme at `at`.
+    /// Returns the byte range of the previous string which got replaced, which is empty.
+    pub fn insert(&mut self, at: GraphemeIdx, string: &str) -> Range<ByteIdx> {
         debug_assert!(at.saturating_sub(1) <= self.grapheme_count());
-        if let Some(fragment) = self.fragments.get(at) {
-            self.string.insert_str(fragment.start_byte_idx, string);
-        } else {
-            self.string.push_str(string);
-        }
-        self.rebuild_fragments();
+        let byte_idx = self
+            .fragments
+            .get(at)
+            .map_or(self.string.len(), |fragment| fragment.start_byte_idx);
+        self.replace_range(byte_idx..byte_idx, string);
+        byte_idx..byte_idx
     }
-    pub fn remove(&mut self, start: GraphemeIdx, length: GraphemeIdx) {
+    /// Removes `length` graphemes starting at `start`.
+    /// Returns the byte range of the previous string which got removed.
+    pub fn remove(&mut self, start: GraphemeIdx, length: GraphemeIdx) -> Range<ByteIdx> {
         debug_assert!(start <= self.grapheme_count());
-        if let Some(start_fragment) = self.fragments.get(start) {
-            let end = start.saturating_add(length);
-            let start_byte_idx = start_fragment.start_byte_idx;
-            if let Some(end_fragment) = self.fragments.get(end) {
-                let end_byte_idx = end_fragment.start_byte_idx;
-                self.string.drain(start_byte_idx..end_byte_idx);
-            } else {
-                self.string.drain(start_byte_idx..);
-            }
-            self.rebuild_fragments();
-        }
+        let Some(start_fragment) = self.fragments.get(start) else {
+            return self.string.len()..self.string.len();
+        };
+        let end = start.saturating_add(length);
+        let start_byte_idx = start_fragment.start_byte_idx;
+        let end_byte_idx = self
+            .fragments
+            .get(end)
+            .map_or(self.string.len(), |fragment| fragment.start_byte_idx);
+        self.replace_range(start_byte_idx..end_byte_idx, "");
+        start_byte_idx..end_byte_idx
     }
     pub fn append(&mut self, other: &Self) {
-        self.insert(self.len(), &other.string);
+        self.insert(self.grapheme_count(), &other.string);
     }
 
     pub fn split_off(&mut self, at: GraphemeIdx) -> Self {
         if let Some(fragment) = self.fragments.get(at) {
-            let remainder = self.string.split_off(fragment.start_byte_idx);
-            self.rebuild_fragments();
-            Self::from(&remainder)
+            // both halves keep their grapheme boundaries, so the fragments are only moved
+            let split_byte_idx = fragment.start_byte_idx;
+            let remainder = self.string.split_off(split_byte_idx);
+            let mut fragments = self.fragments.split_off(at);
+            for fragment in &mut fragments {
+                fragment.start_byte_idx = fragment.start_byte_idx.saturating_sub(split_byte_idx);
+            }
+            Self::update_tab_widths(&mut fragments, self.tab_width);
+            Self {
+                fragments,
+                string: remainder,
+                tab_width: self.tab_width,
+            }
         } else {
-            Self::default()
+            Self::with_tab_width("", self.tab_width)
         }
     }
     fn byte_idx_to_grapheme_idx(&self, byte_idx: ByteIdx) -> Option<GraphemeIdx> {
@@ -184,11 +310,14 @@ impl Line {
             .iter()
             .position(|fragment| fragment.start_byte_idx >= byte_idx)
     }
-    fn grapheme_idx_to_byte_idx(&self, grapheme_idx: GraphemeIdx) -> ByteIdx {
+    pub fn grapheme_idx_to_byte_idx(&self, grapheme_idx: GraphemeIdx) -> ByteIdx {
         debug_assert!(grapheme_idx <= self.grapheme_count());
         if grapheme_idx == 0 || self.grapheme_count() == 0 {
             return 0;
         }
+        if grapheme_idx == self.grapheme_count() {
+            return self.string.len();
+        }
         self.fragments.get(grapheme_idx).map_or_else(
             || {
                 #[cfg(debug_assertions)]
@@ -285,3 +414,123 @@ impl Deref for Line {
         &self.string
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    // pieces which interact across grapheme boundaries: combining marks, joiners,
+    // regional indicators (flags), skin tone modifiers, wide characters and tabs
+    const PIECES: &[&str] = &[
+        "a",
+        " ",
+        "\t",
+        "é",
+        "e\u{301}",
+        "\u{301}",
+        "\u{200d}",
+        "👍",
+        "\u{1f3fb}",
+        "👨\u{200d}👩",
+        "🇦",
+        "🇧",
+        "緑",
+        "\r",
+        "\u{a0}",
+    ];
+
+    fn text() -> impl Strategy<Value = String> {
+        prop::collection::vec(prop::sample::select(PIECES), 0..8).prop_map(|pieces| pieces.concat())
+    }
+
+    #[derive(Clone, Debug)]
+    enum Edit {
+        Insert(usize, String),
+        Remove(usize, usize),
+        Append(String),
+        SplitOff(usize),
+    }
+
+    fn edit() -> impl Strategy<Value = Edit> {
+        prop_oneof![
+            (any::<usize>(), text()).prop_map(|(at, text)| Edit::Insert(at, text)),
+            (any::<usize>(), 0..4_usize).prop_map(|(at, length)| Edit::Remove(at, length)),
+            text().prop_map(Edit::Append),
+            any::<usize>().prop_map(Edit::SplitOff),
+        ]
+    }
+
+    proptest! {
+        #[test]
+        fn test_incremental_fragments_match_rebuild(
+            initial in text(),
+            edits in prop::collection::vec(edit(), 1..12),
+            tab_width in 1..5_usize,
+        ) {
+            let mut line = Line::with_tab_width(&initial, tab_width);
+            for edit in edits {
+                // any grapheme index up to and including the end of the line
+                let clamp = |at: usize| at.checked_rem(line.grapheme_count().saturating_add(1)).unwrap_or(0);
+                let before = line.string.clone();
+                // the returned range is all that changed in the string
+                let replaced = match edit {
+                    Edit::Insert(at, text) => Some(line.insert(clamp(at), &text)),
+                    Edit::Remove(at, length) => Some(line.remove(clamp(at), length)),
+                    Edit::Append(text) => {
+                        line.append(&Line::from(&text));
+                        None
+                    }
+                    Edit::SplitOff(at) => {
+                        let remainder = line.split_off(clamp(at));
+                        let rebuilt = Line::with_tab_width(&remainder.string, tab_width);
+                        prop_assert_eq!(&remainder.fragments, &rebuilt.fragments);
+                        None
+                    }
+                };
+                if let Some(replaced) = replaced {
+                    let end = replaced
+                        .end
+                        .saturating_add(line.string.len())
+                        .saturating_sub(before.len());
+                    let patched = format!(
+                        "{}{}{}",
+                        &before[..replaced.start],
+                        &line.string[replaced.start..end],
+                        &before[replaced.end..]
+                    );
+                    prop_assert_eq!(&patched, &line.string);
+                }
+                let rebuilt = Line::with_tab_width(&line.string, tab_width);
+                prop_assert_eq!(&line.fragments, &rebuilt.fragments, "{:?}", line.string);
+            }
+        }
+    }
+
+    #[test]
+    fn test_tab_stops() {
+        let line = Line::with_tab_width("\ta\tbc\t", 4);
+        assert_eq!(line.width_until(1), 4);
+        assert_eq!(line.width_until(2), 5);
+        assert_eq!(line.width_until(3), 8);
+        assert_eq!(line.width(), 12);
+        assert_eq!(line.get_visible_graphemes(0..12), "→   a→  bc→ ");
+
+        let mut line = Line::with_tab_width("ab\tc", 8);
+        assert_eq!(line.width(), 9);
+        line.set_tab_width(2);
+        assert_eq!(line.width(), 5);
+        line.insert(0, "x");
+        assert_eq!(line.width(), 5);
+        assert_eq!(line.get_visible_graphemes(0..5), "xab→c");
+    }
+
+    #[test]
+    fn test_grapheme_idx_at() {
+        let line = Line::with_tab_width("a\t緑b", 4);
+        let graphemes: Vec<GraphemeIdx> = (0..8).map(|col| line.grapheme_idx_at(col)).collect();
+        // the tab covers columns 1 to 3, the wide character columns 4 and 5
+        assert_eq!(graphemes, [0, 1, 1, 1, 2, 2, 3, 4]);
+        assert_eq!(Line::from("").grapheme_idx_at(3), 0);
+    }
+}
@@ -2,7 +2,7 @@ use super::GraphemeWidth;
 use crate::prelude::ByteIdx;
 use unicode_width::UnicodeWidthStr;
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub struct TextFragment {
     pub grapheme: String,
     pub width: GraphemeWidth,
@@ -0,0 +1,127 @@
+use super::{AnnotatedString, AnnotationType};
+
+// above this many cells, the diff falls back to replacing the whole changed block
+const MAX_LCS_CELLS: usize = 4_000_000;
+
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum DiffLine<'a> {
+    Same(&'a str),
+    Removed(&'a str),
+    Added(&'a str),
+}
+
+/// Computes a line based diff from `old` to `new`,
+/// using the longest common subsequence of the lines which differ.
+pub fn diff_lines<'a>(old: &[&'a str], new: &[&'a str]) -> Vec<DiffLine<'a>> {
+    let prefix_len = old
+        .iter()
+        .zip(new)
+        .take_while(|(old_line, new_line)| old_line == new_line)
+        .count();
+    let (old_rest, new_rest) = (&old[prefix_len..], &new[prefix_len..]);
+    let suffix_len = old_rest
+        .iter()
+        .rev()
+        .zip(new_rest.iter().rev())
+        .take_while(|(old_line, new_line)| old_line == new_line)
+        .count();
+    let old_middle = &old_rest[..old_rest.len().saturating_sub(suffix_len)];
+    let new_middle = &new_rest[..new_rest.len().saturating_sub(suffix_len)];
+
+    let mut result: Vec<DiffLine> = old[..prefix_len]
+        .iter()
+        .map(|line| DiffLine::Same(line))
+        .collect();
+    result.extend(diff_middle(old_middle, new_middle));
+    result.extend(
+        old_rest[old_middle.len()..]
+            .iter()
+            .map(|line| DiffLine::Same(line)),
+    );
+    result
+}
+
+fn diff_middle<'a>(old: &[&'a str], new: &[&'a str]) -> Vec<DiffLine<'a>> {
+    let width = new.len().saturating_add(1);
+    let cells = old.len().saturating_add(1).saturating_mul(width);
+    if cells > MAX_LCS_CELLS {
+        let removed = old.iter().map(|line| DiffLine::Removed(line));
+        return removed
+            .chain(new.iter().map(|line| DiffLine::Added(line)))
+            .collect();
+    }
+    // lcs[i * width + j]: length of the longest common subsequence of old[i..] and new[j..]
+    let mut lcs = vec![0_usize; cells];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            let cell = i.saturating_mul(width).saturating_add(j);
+            let below = cell.saturating_add(width);
+            lcs[cell] = if old[i] == new[j] {
+                lcs[below.saturating_add(1)].saturating_add(1)
+            } else {
+                lcs[below].max(lcs[cell.saturating_add(1)])
+            };
+        }
+    }
+    let mut result = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() && j < new.len() {
+        let cell = i.saturating_mul(width).saturating_add(j);
+        if old[i] == new[j] {
+            result.push(DiffLine::Same(old[i]));
+            i = i.saturating_add(1);
+            j = j.saturating_add(1);
+        } else if lcs[cell.saturating_add(width)] >= lcs[cell.saturating_add(1)] {
+            result.push(DiffLine::Removed(old[i]));
+            i = i.saturating_add(1);
+        } else {
+            result.push(DiffLine::Added(new[j]));
+            j = j.saturating_add(1);
+        }
+    }
+    result.extend(old[i..].iter().map(|line| DiffLine::Removed(line)));
+    result.extend(new[j..].iter().map(|line| DiffLine::Added(line)));
+    result
+}
+
+/// Formats the diff like `diff -u` does, without hunk headers, to be shown in a `Pager`.
+pub fn diff_to_annotated_strings(diff: &[DiffLine]) -> Vec<AnnotatedString> {
+    diff.iter()
+        .map(|line| {
+            let (prefix, text, annotation_type) = match line {
+                DiffLine::Same(text) => (' ', text, None),
+                DiffLine::Removed(text) => ('-', text, Some(AnnotationType::Removed)),
+                DiffLine::Added(text) => ('+', text, Some(AnnotationType::Added)),
+            };
+            let string = format!("{prefix}{text}");
+            let mut result = AnnotatedString::from(&string);
+            if let Some(annotation_type) = annotation_type {
+                result.push(annotation_type, 0, string.len());
+            }
+            result
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use DiffLine::{Added, Removed, Same};
+
+    #[test]
+    fn test_diff_lines() {
+        assert_eq!(diff_lines(&[], &[]), vec![]);
+        assert_eq!(
+            diff_lines(&["a", "b", "c"], &["a", "c", "d"]),
+            vec![Same("a"), Removed("b"), Same("c"), Added("d")]
+        );
+        assert_eq!(
+            diff_lines(&["x", "a", "y"], &["a", "z"]),
+            vec![Removed("x"), Same("a"), Removed("y"), Added("z")]
+        );
+        assert_eq!(
+            diff_lines(&["a", "b"], &["a", "b"]),
+            vec![Same("a"), Same("b")]
+        );
+    }
+}
@@ -0,0 +1,53 @@
+use std::fmt::{Display, Formatter, Result};
+
+#[derive(Default, Eq, PartialEq, Debug, Copy, Clone)]
+pub enum LineEnding {
+    #[default]
+    Lf,
+    CrLf,
+}
+
+impl LineEnding {
+    /// Detects the line ending style by the first line break of the contents.
+    pub fn detect(contents: &str) -> Self {
+        match contents.find('\n') {
+            Some(idx) if contents[..idx].ends_with('\r') => Self::CrLf,
+            _ => Self::Lf,
+        }
+    }
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Lf => "\n",
+            Self::CrLf => "\r\n",
+        }
+    }
+    pub fn toggle(self) -> Self {
+        match self {
+            Self::Lf => Self::CrLf,
+            Self::CrLf => Self::Lf,
+        }
+    }
+}
+
+impl Display for LineEnding {
+    fn fmt(&self, formatter: &mut Formatter) -> Result {
+        match self {
+            Self::Lf => write!(formatter, "LF"),
+            Self::CrLf => write!(formatter, "CRLF"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect() {
+        assert_eq!(LineEnding::detect(""), LineEnding::Lf);
+        assert_eq!(LineEnding::detect("a\nb\r\n"), LineEnding::Lf);
+        assert_eq!(LineEnding::detect("a\r\nb\n"), LineEnding::CrLf);
+        assert_eq!(LineEnding::detect("\r\n"), LineEnding::CrLf);
+        assert_eq!(LineEnding::detect("no newline\r"), LineEnding::Lf);
+    }
+}
@@ -1,20 +1,20 @@
 // 日本語🇯🇵の表示テスト
 use crate::prelude::*;
 use crossterm::event::{
-    read,
+    poll, read,
     Event::{self, Key},
-    KeyEvent,
-    KeyEventKind,
+    KeyEvent, KeyEventKind,
 };
 use terminal::Terminal;
 mod command;
 mod file_info;
 mod terminal;
 use command::{
-    Command::{self, Edit, Move, System},
-    Edit::InsertNewLine,
+    Command::{self, Edit, Mouse, Move, System},
+    Edit::{Insert, InsertNewLine, ToggleLineEnding},
+    Mouse::{Click, Drag, ScrollDown, ScrollUp},
     Move::{Down, Left, Right, Up},
-    System::{Dismiss, Quit, Resize, Save, Search},
+    System::{Dismiss, Quit, Reload, Resize, Save, SaveAs, Search, ShowMessages},
 };
 mod ui_components;
 use annotated_string::AnnotatedString;
@@ -23,7 +23,9 @@ mod annotation;
 use annotation::Annotation;
 pub use annotation_type::AnnotationType;
 pub mod annotation_type;
-use std::io::Error;
+use std::fs::{self, create_dir_all, remove_file};
+use std::io::{stderr, stdin, stdout, Error, ErrorKind, IsTerminal, Read, Write};
+use std::path::Path;
 mod line;
 use line::Line;
 mod file_type;
@@ -32,14 +34,54 @@ mod hex_color;
 use hex_color::HexColor;
 mod document_status;
 use document_status::DocumentStatus;
-use ui_components::{CommandBar, MessageBar, StatusBar, UIComponent, View};
+mod settings;
+use settings::Settings;
+mod cli_args;
+use cli_args::CliArgs;
+mod line_ending;
+use line_ending::LineEnding;
+mod timer_queue;
+use timer_queue::{Timer, TimerQueue};
+mod atomic_write;
+use atomic_write::Written;
+mod is_writable;
+use is_writable::is_writable;
+mod io_error_message;
+use io_error_message::{describe_save_error, missing_parent_dir};
+mod line_diff;
+use line_diff::{diff_lines, diff_to_annotated_strings};
+mod swap_file;
+use swap_file::{StaleSwapFile, SwapFile};
+mod disk_state;
+use disk_state::DiskState;
+mod text_encoding;
+use event_log::EventRecorder;
+mod event_log;
+use std::time::{Duration, Instant};
+use text_encoding::{looks_binary, TextEncoding};
+use ui_components::{CommandBar, HexView, MessageBar, Pager, StatusBar, UIComponent, View};
 
 const QUIT_COUNT: u8 = 2;
+// lines scrolled per turn of the mouse wheel
+const SCROLL_LINES: usize = 3;
+const SWAP_FILE_INTERVAL: Duration = Duration::from_secs(4);
+const FILE_CHECK_INTERVAL: Duration = Duration::from_secs(2);
 
-#[derive(Default, Eq, PartialEq)]
+#[derive(Debug, Default, Eq, PartialEq)]
 enum PromptType {
     Save,
     Search,
+    // ask whether to create the missing directories of the file path
+    CreateDirectory,
+    // ask what to do with the swap file of an earlier session
+    Recover,
+    // ask what to do about the file having been changed by another program;
+    // `on_save` is set if this came up when saving
+    FileChanged {
+        on_save: bool,
+    },
+    // ask whether to discard unsaved changes when reloading
+    Reload,
     #[default]
     None,
 }
@@ -60,6 +102,18 @@ pub struct Editor {
     title: String,
     quit_count: u8,
     prompt_type: PromptType,
+    timers: TimerQueue,
+    // shown in place of the view while present
+    pager: Option<Pager>,
+    swap_file: Option<SwapFile>,
+    // the swap file left by another session, until the user decided what to do with it
+    stale_swap_file: Option<StaleSwapFile>,
+    swap_file_error_reported: bool,
+    // shown in place of the view for binary files
+    hex_view: Option<HexView>,
+    // write the buffer to stdout when quitting, instead of asking to save it
+    to_stdout: bool,
+    recorder: Option<EventRecorder>,
 }
 
 impl Editor {
@@ -69,31 +123,82 @@ impl Editor {
             let _ = Terminal::terminate();
             current_hook(panic_info);
         }));
+        let args = CliArgs::parse(std::env::args().skip(1));
+        // read stdin before the terminal takes over; input is read from /dev/tty afterwards
+        let from_stdin = args.filename.as_deref() == Some("-")
+            || (args.filename.is_none() && !stdin().is_terminal());
+        let stdin_contents = from_stdin.then(|| {
+            let mut contents = Vec::new();
+            stdin().read_to_end(&mut contents).map(|_| contents)
+        });
+        Self::with_args(&args, stdin_contents)
+    }
+    /// Sets up the editor for the given arguments on the terminal.
+    /// `stdin_contents` is the buffer read from stdin, if it was requested.
+    fn with_args(
+        args: &CliArgs,
+        stdin_contents: Option<Result<Vec<u8>, Error>>,
+    ) -> Result<Self, Error> {
         Terminal::initialize()?;
+        if args.settings.mouse {
+            Terminal::enable_mouse_capture()?;
+        }
 
         let mut editor = Self::default();
         editor.reset_quit_count();
+        editor.to_stdout = args.to_stdout;
 
-        let args: Vec<String> = std::env::args().collect();
-        // only load the first file for now
-        if let Some(first) = args.get(1) {
+        editor.view.set_settings(args.settings);
+        if let Some(stdin_contents) = stdin_contents {
+            match stdin_contents {
+                Ok(contents) => editor.view.load_unnamed(&contents),
+                Err(err) => editor
+                    .message_bar
+                    .update_error(&format!("Could not read stdin: {err}")),
+            }
+        } else if let Some(first) = &args.filename {
             debug_assert!(!first.is_empty());
-            let message = if editor.view.load(first).is_err() {
-                &format!("Could not open file: {first}")
-            } else {
-                "HELP: Ctrl-G = find | Ctrl-S = save | Ctrl-Q = quit"
-            };
-
-            editor.message_bar.update_message(message);
+            match editor.view.load(first) {
+                Err(err) if err.kind() == ErrorKind::InvalidData => {
+                    editor.open_hex_view(first, args.settings);
+                }
+                Err(_) => editor
+                    .message_bar
+                    .update_error(&format!("Could not open file: {first}")),
+                Ok(()) if editor.view.buffer.is_lossy() => editor.warn_read_only(),
+                Ok(()) => editor.message_bar.update_message(
+                    "HELP: Ctrl-G = find | Ctrl-S = save | Ctrl-O = messages | Ctrl-Q = quit",
+                ),
+            }
+        }
+        for error in &args.errors {
+            editor.message_bar.update_warning(error);
         }
+        editor.check_stale_swap_file();
+        let now = Instant::now();
+        editor
+            .timers
+            .schedule_repeating(Timer::SwapFile, SWAP_FILE_INTERVAL, now);
+        editor
+            .timers
+            .schedule_repeating(Timer::FileCheck, FILE_CHECK_INTERVAL, now);
         let size = Terminal::size().unwrap_or_default();
         editor.handle_resize_command(size);
+        if let Some(path) = &args.record {
+            match EventRecorder::create(path, size) {
+                Ok(recorder) => editor.recorder = Some(recorder),
+                Err(err) => editor
+                    .message_bar
+                    .update_warning(&format!("Could not record to {path}: {err}")),
+            }
+        }
         editor.refresh_status();
         Ok(editor)
     }
 
     pub fn handle_resize_command(&mut self, size: Size) {
         self.terminal_size = size;
+        let _ = Terminal::resize(size);
         let view_size = Size {
             width: size.width,
             height: size.height.saturating_sub(2),
@@ -103,13 +208,19 @@ impl Editor {
             height: 1,
         };
         self.view.resize(view_size);
+        if let Some(pager) = &mut self.pager {
+            pager.resize(view_size);
+        }
+        if let Some(hex_view) = &mut self.hex_view {
+            hex_view.resize(view_size);
+        }
         self.status_bar.resize(bar_size);
         self.message_bar.resize(bar_size);
         self.command_bar.resize(bar_size);
     }
 
     pub fn refresh_status(&mut self) {
-        self.status_bar.update_status(&self.view);
+        self.update_status();
         let filename = self.status_bar.document_status.filename_string();
         let title = format!("{filename} - {NAME}");
         if title != self.title && Terminal::set_title(&title).is_ok() {
@@ -123,9 +234,10 @@ impl Editor {
             if self.should_quit {
                 break;
             }
-            match read() {
-                Ok(event) => self.evaluate_event(event),
-
+            self.schedule_timers();
+            match self.wait_for_event() {
+                Ok(Some(event)) => self.evaluate_event(event),
+                Ok(None) => {}
                 Err(err) => {
                     #[cfg(debug_assertions)]
                     panic!("Could not read event: {err}");
@@ -133,17 +245,136 @@ impl Editor {
                     let _ = err;
                 }
             }
+            for timer in self.timers.pop_expired(Instant::now()) {
+                self.handle_timer(timer);
+            }
+            self.update_status();
+        }
+    }
+    fn update_status(&mut self) {
+        if let Some(hex_view) = &self.hex_view {
+            self.status_bar
+                .set_document_status(hex_view.document_status());
+        } else {
             self.status_bar.update_status(&self.view);
         }
     }
+    /// Waits for the next event, but no longer than until the next timer is due.
+    /// Returns None if a timer is due before any event arrived.
+    fn wait_for_event(&self) -> Result<Option<Event>, Error> {
+        if let Some(timeout) = self.timers.time_until_next(Instant::now()) {
+            if !poll(timeout)? {
+                return Ok(None);
+            }
+        }
+        read().map(Some)
+    }
+    fn schedule_timers(&mut self) {
+        // the message bar is hidden while prompting, and gets cleared when shown again
+        let message_deadline = self
+            .message_bar
+            .expires_at()
+            .filter(|_| self.prompt_type.is_none());
+        if let Some(deadline) = message_deadline {
+            self.timers.schedule_at(Timer::MessageExpiry, deadline);
+        } else {
+            self.timers.cancel(Timer::MessageExpiry);
+        }
+    }
+    fn handle_timer(&mut self, timer: Timer) {
+        match timer {
+            Timer::MessageExpiry => self.message_bar.set_needs_redraw(true),
+            Timer::SwapFile => self.update_swap_file(),
+            Timer::FileCheck => self.check_file_changed(),
+        }
+    }
+    fn check_file_changed(&mut self) {
+        // don't interrupt the user
+        if !self.prompt_type.is_none() || self.pager.is_some() {
+            return;
+        }
+        if self.view.buffer.file_info.has_undismissed_change() {
+            self.show_prompt(PromptType::FileChanged { on_save: false });
+        }
+    }
+    fn check_stale_swap_file(&mut self) {
+        let Some(path) = self.view.buffer.file_info.get_path() else {
+            return;
+        };
+        let swap_file = SwapFile::new(path);
+        if let Some(stale) = swap_file.find_stale() {
+            if stale.other_host.is_none() && stale.contents == self.view.buffer.contents() {
+                // nothing to recover
+                let _ = remove_file(stale.path);
+            } else {
+                self.stale_swap_file = Some(stale);
+                self.show_prompt(PromptType::Recover);
+            }
+        } else if swap_file.is_in_use() {
+            self.message_bar
+                .update_warning("The file is being edited in another session.");
+        }
+        self.swap_file = Some(swap_file);
+    }
+    fn update_swap_file(&mut self) {
+        // don't overwrite a swap file which has not been dealt with yet
+        if self.stale_swap_file.is_some() {
+            return;
+        }
+        let buffer = &self.view.buffer;
+        let Some(path) = buffer.file_info.get_path() else {
+            return;
+        };
+        if !self
+            .swap_file
+            .as_ref()
+            .is_some_and(|swap_file| swap_file.is_for(path))
+        {
+            // the buffer has been saved under a new name
+            if let Some(swap_file) = &mut self.swap_file {
+                swap_file.remove();
+            }
+            self.swap_file = Some(SwapFile::new(path));
+        }
+        let Some(swap_file) = &mut self.swap_file else {
+            return;
+        };
+        if buffer.dirty == 0 {
+            swap_file.remove();
+            return;
+        }
+        match swap_file.update(buffer.dirty, || buffer.contents()) {
+            Ok(()) => self.swap_file_error_reported = false,
+            Err(err) if !self.swap_file_error_reported => {
+                self.swap_file_error_reported = true;
+                self.message_bar
+                    .update_warning(&format!("Could not write swap file: {err}"));
+            }
+            Err(_) => {}
+        }
+    }
 
     // needless_pass_by_value: Event is not huge, so there is not a performance overhead in passing
     // by value, and pattern matching on it is more ergonomic.
     #[allow(clippy::needless_pass_by_value)]
     fn evaluate_event(&mut self, event: Event) {
+        if let Some(recorder) = &mut self.recorder {
+            if let Err(err) = recorder.record(&event) {
+                self.recorder = None;
+                self.message_bar
+                    .update_warning(&format!("Stopped recording: {err}"));
+            }
+        }
         let should_process = match event {
-            Key(KeyEvent { kind, .. }) => kind == KeyEventKind::Press,
-            Event::Resize(_, _) => true,
+            // releasing a key does nothing, and is only reported with the enhanced keyboard
+            // protocol; held keys repeat like presses
+            Key(KeyEvent {
+                kind: KeyEventKind::Release,
+                ..
+            }) => return,
+            // moving the mouse, releasing a button and the other buttons do nothing
+            Event::Mouse(mouse_event) if command::Mouse::try_from(mouse_event).is_err() => return,
+            Key(_) | Event::Resize(_, _) | Event::Mouse(_) => true,
             _ => false,
         };
         if should_process {
@@ -156,7 +387,7 @@ impl Editor {
             }
         } else {
             #[cfg(debug_assertions)]
-            panic!("Received and discarded unsupported or non-press event.");
+            panic!("Received and discarded unsupported event.");
         }
     }
     fn process_command(&mut self, command: Command) {
@@ -164,9 +395,27 @@ impl Editor {
             self.handle_resize_command(size);
             return;
         }
+        if let Mouse(mouse) = command {
+            self.process_mouse_command(mouse);
+            return;
+        }
+        if self.pager.is_some() {
+            self.process_command_in_pager(command);
+            return;
+        }
+        if self.hex_view.is_some() {
+            self.process_command_in_hex_view(command);
+            return;
+        }
         match self.prompt_type {
             PromptType::Search => self.process_command_during_search(command),
             PromptType::Save => self.process_command_during_save(command),
+            PromptType::CreateDirectory => self.process_command_during_create_directory(command),
+            PromptType::Reload => self.process_command_during_reload(command),
+            PromptType::Recover => self.process_command_during_recover(command),
+            PromptType::FileChanged { on_save } => {
+                self.process_command_during_file_changed(command, on_save);
+            }
             PromptType::None => self.process_command_during_no_prompt(command),
         }
     }
@@ -178,17 +427,190 @@ impl Editor {
         self.reset_quit_count();
         // handle edit / move commands or start save / search
         match command {
+            // a read-only file can still be saved under another name
+            System(Save) | Edit(_) if self.view.buffer.is_read_only() => self.warn_read_only(),
             System(Save) => {
-                if self.view.buffer.file_info.has_path() {
+                if self.view.buffer.file_info.changed_disk_state().is_some() {
+                    self.show_prompt(PromptType::FileChanged { on_save: true });
+                } else if self.view.buffer.file_info.has_path() {
                     self.save(None);
                 } else {
                     self.show_prompt(PromptType::Save);
                 }
             }
+            System(SaveAs) => self.show_prompt(PromptType::Save),
             System(Search) => self.show_prompt(PromptType::Search),
+            System(ShowMessages) => {
+                self.show_pager(Pager::new("Messages", self.message_bar.history()));
+            }
+            System(Reload) => {
+                if !self.view.buffer.file_info.has_path() {
+                    self.message_bar.update_warning("No file to reload.");
+                } else if self.view.buffer.dirty > 0 {
+                    self.show_prompt(PromptType::Reload);
+                } else {
+                    self.reload();
+                }
+            }
+            Edit(ToggleLineEnding) => {
+                self.view.handle_edit_command(ToggleLineEnding);
+                self.message_bar.update_message(&format!(
+                    "Converted line endings to {}",
+                    self.view.buffer.line_ending
+                ));
+            }
             Edit(command) => self.view.handle_edit_command(command),
             Move(command) => self.view.handle_move_command(command),
-            System(_) => {}
+            System(_) | Mouse(_) => {}
+        }
+    }
+    fn process_mouse_command(&mut self, mouse: command::Mouse) {
+        if self.pager.is_some() || self.hex_view.is_some() {
+            // these only scroll, by moving
+            let direction = match mouse {
+                ScrollUp => Up,
+                ScrollDown => Down,
+                Click(_) | Drag(_) => return,
+            };
+            for _ in 0..SCROLL_LINES {
+                self.process_command(Move(direction));
+            }
+            return;
+        }
+        if !self.prompt_type.is_none() {
+            return;
+        }
+        self.reset_quit_count();
+        let status_bar_row = self.terminal_size.height.saturating_sub(2);
+        match mouse {
+            Click(position) if position.row < status_bar_row => self.view.click(position),
+            Click(position)
+                if position.row == status_bar_row
+                    && self.status_bar.is_line_ending_at(position.col) =>
+            {
+                self.process_command_during_no_prompt(Edit(ToggleLineEnding));
+            }
+            Click(_) => {}
+            Drag(position) => self.view.drag(position),
+            ScrollUp => self.view.scroll_up(SCROLL_LINES),
+            ScrollDown => self.view.scroll_down(SCROLL_LINES),
+        }
+    }
+    fn process_command_in_pager(&mut self, command: Command) {
+        match command {
+            System(Dismiss | Quit | ShowMessages) => self.close_pager(),
+            Move(command) => {
+                if let Some(pager) = &mut self.pager {
+                    pager.handle_move_command(command);
+                }
+            }
+            _ => {}
+        }
+    }
+    fn open_hex_view(&mut self, filename: &str, settings: Settings) {
+        match HexView::load(filename, settings) {
+            Ok(hex_view) => {
+                self.hex_view = Some(hex_view);
+                self.message_bar.update_message(
+                    "Binary file: type hex digits to change bytes | Ctrl-S = save | Ctrl-Q = quit",
+                );
+            }
+            Err(err) => self
+                .message_bar
+                .update_error(&format!("Could not open file {filename}: {err}")),
+        }
+    }
+    fn process_command_in_hex_view(&mut self, command: Command) {
+        if matches!(self.prompt_type, PromptType::FileChanged { .. }) {
+            self.process_command_during_hex_file_changed(command);
+            return;
+        }
+        if matches!(command, System(Quit)) {
+            self.handle_quit();
+            return;
+        }
+        self.reset_quit_count();
+        let Some(hex_view) = &mut self.hex_view else {
+            return;
+        };
+        match command {
+            System(Save) | Edit(_) if hex_view.is_read_only() => {
+                self.message_bar
+                    .update_warning("The file is read-only and cannot be changed.");
+            }
+            System(Save) if hex_view.changed_disk_state().is_some() => {
+                self.show_prompt(PromptType::FileChanged { on_save: true });
+            }
+            System(Save) => self.save_hex_view(),
+            System(ShowMessages) => {
+                self.show_pager(Pager::new("Messages", self.message_bar.history()));
+            }
+            Edit(Insert(c)) => {
+                if !hex_view.overwrite_nibble(c) {
+                    self.message_bar
+                        .update_warning("Type hex digits 0-9 and a-f to change bytes.");
+                }
+            }
+            Edit(_) => self
+                .message_bar
+                .update_warning("Bytes can only be overwritten in the hex view."),
+            Move(command) => hex_view.handle_move_command(command),
+            System(_) | Mouse(_) => {}
+        }
+    }
+    fn save_hex_view(&mut self) {
+        let Some(hex_view) = &mut self.hex_view else {
+            return;
+        };
+        match hex_view.save() {
+            Ok(written) => Self::report_saved(&mut self.message_bar, written),
+            Err(err) => {
+                let message = describe_save_error(&err, hex_view.path());
+                self.message_bar.update_error(&message);
+            }
+        }
+    }
+    /// Like `process_command_during_file_changed`, but there is no diff of binary files.
+    fn process_command_during_hex_file_changed(&mut self, command: Command) {
+        match command {
+            Edit(Insert('r' | 'R')) => {
+                self.show_prompt(PromptType::None);
+                let Some(hex_view) = &mut self.hex_view else {
+                    return;
+                };
+                match hex_view.reload() {
+                    Ok(()) => self
+                        .message_bar
+                        .update_message("Reloaded the file from disk."),
+                    Err(err) => self
+                        .message_bar
+                        .update_error(&format!("Could not reload file: {err}")),
+                }
+            }
+            Edit(Insert('k' | 'K')) => {
+                self.show_prompt(PromptType::None);
+                self.save_hex_view();
+            }
+            System(Dismiss) => {
+                self.show_prompt(PromptType::None);
+                self.message_bar.update_message("Aborted.");
+            }
+            _ => {}
+        }
+    }
+    fn show_pager(&mut self, mut pager: Pager) {
+        pager.resize(Size {
+            width: self.terminal_size.width,
+            height: self.terminal_size.height.saturating_sub(2),
+        });
+        pager.scroll_to_end();
+        self.pager = Some(pager);
+    }
+    fn close_pager(&mut self) {
+        self.pager = None;
+        self.view.set_needs_redraw(true);
+        if let Some(hex_view) = &mut self.hex_view {
+            hex_view.set_needs_redraw(true);
         }
     }
     fn process_command_during_save(&mut self, command: Command) {
@@ -198,14 +620,163 @@ impl Editor {
                 self.message_bar.update_message("Aborted.");
             }
             Edit(InsertNewLine) => {
-                self.save(Some(&self.command_bar.value()));
-                // it can't set None here because of multiple mutable borrows
+                let filename = self.command_bar.value();
+                // hide the prompt first, as saving may show another one
                 self.show_prompt(PromptType::None);
+                self.save(Some(&filename));
             }
             Edit(command) => self.command_bar.handle_edit_command(command),
             _ => {}
         }
     }
+    fn process_command_during_file_changed(&mut self, command: Command, on_save: bool) {
+        match command {
+            Edit(Insert('r' | 'R')) => {
+                self.show_prompt(PromptType::None);
+                self.reload();
+            }
+            Edit(Insert('k' | 'K')) => {
+                self.show_prompt(PromptType::None);
+                // don't ask again about this change
+                let file_info = &mut self.view.buffer.file_info;
+                if let Some(disk_state) = file_info.changed_disk_state() {
+                    file_info.set_disk_state(Some(disk_state));
+                }
+                if on_save {
+                    self.save(None);
+                } else {
+                    self.message_bar
+                        .update_message("Kept your version. Saving will overwrite the file.");
+                }
+            }
+            Edit(Insert('d' | 'D')) => {
+                let Some(path) = self.view.buffer.file_info.get_path() else {
+                    return;
+                };
+                match fs::read(path) {
+                    Ok(bytes) => {
+                        let (disk_contents, _, _) = TextEncoding::decode(&bytes);
+                        let contents = self.view.buffer.contents();
+                        let old: Vec<&str> = disk_contents.lines().collect();
+                        let new: Vec<&str> = contents.lines().collect();
+                        let diff = diff_lines(&old, &new);
+                        self.show_pager(Pager::new(
+                            "Your version compared to the file on disk",
+                            diff_to_annotated_strings(&diff),
+                        ));
+                    }
+                    Err(err) => self
+                        .message_bar
+                        .update_error(&format!("Could not read file: {err}")),
+                }
+            }
+            System(Dismiss) => {
+                self.show_prompt(PromptType::None);
+                self.view.buffer.file_info.dismiss_changed_disk_state();
+                self.message_bar.update_message("Aborted.");
+            }
+            _ => {}
+        }
+    }
+    fn reload(&mut self) {
+        match self.view.reload() {
+            Ok(()) => self
+                .message_bar
+                .update_message("Reloaded the file from disk."),
+            Err(err) => self
+                .message_bar
+                .update_error(&format!("Could not reload file: {err}")),
+        }
+    }
+    fn process_command_during_recover(&mut self, command: Command) {
+        match command {
+            Edit(Insert('r' | 'R')) => {
+                self.show_prompt(PromptType::None);
+                if let Some(stale) = self.stale_swap_file.take() {
+                    self.view.recover(&stale.contents);
+                    if let Some(swap_file) = &mut self.swap_file {
+                        swap_file.take_over(stale.path);
+                    }
+                    self.message_bar
+                        .update_message("Recovered unsaved changes from the swap file.");
+                }
+            }
+            Edit(Insert('d' | 'D')) => {
+                if let Some(stale) = &self.stale_swap_file {
+                    let file_contents = self.view.buffer.contents();
+                    let old: Vec<&str> = file_contents.lines().collect();
+                    let new: Vec<&str> = stale.contents.lines().collect();
+                    let diff = diff_lines(&old, &new);
+                    self.show_pager(Pager::new(
+                        "Changes in the swap file",
+                        diff_to_annotated_strings(&diff),
+                    ));
+                }
+            }
+            Edit(Insert('x' | 'X')) => {
+                self.show_prompt(PromptType::None);
+                if let Some(stale) = self.stale_swap_file.take() {
+                    let _ = remove_file(stale.path);
+                    self.message_bar.update_message("Discarded the swap file.");
+                }
+            }
+            System(Dismiss) => {
+                // keep the swap file so that it can still be recovered later,
+                // while this session protects its own edits in another one
+                self.show_prompt(PromptType::None);
+                if let Some(stale) = self.stale_swap_file.take() {
+                    if let Some(swap_file) = &mut self.swap_file {
+                        swap_file.leave_alone(stale.path);
+                    }
+                    self.message_bar
+                        .update_message("Kept the swap file without recovering it.");
+                }
+            }
+            _ => {}
+        }
+    }
+    fn process_command_during_reload(&mut self, command: Command) {
+        match command {
+            Edit(Insert('y' | 'Y')) => {
+                self.show_prompt(PromptType::None);
+                self.reload();
+            }
+            Edit(Insert('n' | 'N')) | System(Dismiss) => {
+                self.show_prompt(PromptType::None);
+                self.message_bar.update_message("Aborted.");
+            }
+            _ => {}
+        }
+    }
+    fn process_command_during_create_directory(&mut self, command: Command) {
+        match command {
+            Edit(Insert('y' | 'Y')) => {
+                self.show_prompt(PromptType::None);
+                let parent = self
+                    .view
+                    .buffer
+                    .file_info
+                    .get_path()
+                    .and_then(missing_parent_dir)
+                    .map(Path::to_path_buf);
+                if let Some(parent) = parent {
+                    if let Err(err) = create_dir_all(&parent) {
+                        self.message_bar.update_error(&format!(
+                            "Could not create directory {}: {err}",
+                            parent.display()
+                        ));
+                        return;
+                    }
+                }
+                self.save(None);
+            }
+            Edit(Insert('n' | 'N')) | System(Dismiss) => {
+                self.show_prompt(PromptType::None);
+                self.message_bar.update_message("Aborted.");
+            }
+            _ => {}
+        }
+    }
     fn process_command_during_search(&mut self, command: Command) {
         match command {
             System(Dismiss) => {
@@ -229,16 +800,31 @@ impl Editor {
         }
     }
     fn handle_quit(&mut self) {
-        if self.view.buffer.dirty == 0 || self.quit_count == 0 {
+        let dirty = self
+            .hex_view
+            .as_ref()
+            .map_or(self.view.buffer.dirty, |hex_view| hex_view.dirty);
+        if dirty == 0 || self.quit_count == 0 || self.to_stdout {
             self.should_quit = true;
-        } else if self.view.buffer.dirty > 0 {
-            self.message_bar.update_message(&format!(
+        } else {
+            self.message_bar.update_warning(&format!(
                 "Unsaved changes. Press Ctrl-Q {} more times to quit.",
                 self.quit_count,
             ));
             self.quit_count = self.quit_count.saturating_sub(1);
         }
     }
+    fn warn_read_only(&mut self) {
+        if self.view.buffer.is_lossy() {
+            let encoding = self.view.buffer.file_info.get_encoding();
+            self.message_bar.update_warning(&format!(
+                "The file is not valid {encoding}. It is read-only, as saving would lose bytes."
+            ));
+        } else {
+            self.message_bar
+                .update_warning("The file is read-only and cannot be changed.");
+        }
+    }
     fn reset_quit_count(&mut self) {
         if self.quit_count < QUIT_COUNT {
             self.quit_count = QUIT_COUNT;
@@ -248,6 +834,39 @@ impl Editor {
     fn show_prompt(&mut self, prompt_type: PromptType) {
         match prompt_type {
             PromptType::Save => self.command_bar.set_prompt("Save as: "),
+            PromptType::CreateDirectory => {
+                let parent = self
+                    .view
+                    .buffer
+                    .file_info
+                    .get_path()
+                    .and_then(missing_parent_dir)
+                    .map_or_else(String::default, |parent| parent.display().to_string());
+                self.command_bar.set_prompt(&format!(
+                    "Directory {parent} does not exist. Create it? (y/n) "
+                ));
+            }
+            PromptType::FileChanged { .. } if self.hex_view.is_some() => self
+                .command_bar
+                .set_prompt("File changed on disk: (r)eload, (k)eep mine "),
+            PromptType::FileChanged { .. } => self
+                .command_bar
+                .set_prompt("File changed on disk: (r)eload, (k)eep mine, (d)iff "),
+            PromptType::Reload => self
+                .command_bar
+                .set_prompt("Discard unsaved changes and reload? (y/n) "),
+            PromptType::Recover => {
+                let origin = self
+                    .stale_swap_file
+                    .as_ref()
+                    .and_then(|stale| stale.other_host.as_deref())
+                    .map_or_else(String::default, |host| {
+                        format!(" from {host}, which may still be in use")
+                    });
+                self.command_bar.set_prompt(&format!(
+                    "Found unsaved changes in a swap file{origin}: (r)ecover, (d)iff, discard (x) "
+                ));
+            }
             PromptType::Search => {
                 self.view.enter_search();
                 self.command_bar.set_prompt("Search: ");
@@ -265,12 +884,35 @@ impl Editor {
             self.view.save()
         };
 
-        let message = if save_result.is_ok() {
-            "File saved successfully"
-        } else {
-            "Error saving file"
+        let err = match save_result {
+            Ok(written) => {
+                if let Some(swap_file) = &mut self.swap_file {
+                    swap_file.remove();
+                }
+                Self::report_saved(&mut self.message_bar, written);
+                return;
+            }
+            Err(err) => err,
+        };
+        let Some(path) = self.view.buffer.file_info.get_path() else {
+            self.message_bar
+                .update_error(&format!("Error saving file: {err}"));
+            return;
         };
-        self.message_bar.update_message(message);
+        let message = describe_save_error(&err, path);
+        let can_create_parent = missing_parent_dir(path).is_some();
+        self.message_bar.update_error(&message);
+        if can_create_parent {
+            self.show_prompt(PromptType::CreateDirectory);
+        }
+    }
+    fn report_saved(message_bar: &mut MessageBar, written: Written) {
+        match written {
+            Written::Atomically => message_bar.update_message("File saved successfully"),
+            Written::InPlace(reason) => message_bar.update_warning(&format!(
+                "File saved, but overwritten in place, as {reason}."
+            )),
+        }
     }
     fn refresh_screen(&mut self) {
         if self.terminal_size.width == 0 || self.terminal_size.height == 0 {
@@ -287,24 +929,34 @@ impl Editor {
             let terminal_origin = self.terminal_size.height.saturating_sub(2);
             self.status_bar.render(terminal_origin);
             if self.terminal_size.height > 2 {
-                self.view.render(0);
+                if let Some(pager) = &mut self.pager {
+                    pager.render(0);
+                } else if let Some(hex_view) = &mut self.hex_view {
+                    hex_view.render(0);
+                } else {
+                    self.view.render(0);
+                }
             }
         }
 
-        let caret_position = if self.prompt_type.is_none() {
-            self.view.caret_position()
-        } else {
+        let caret_position = if !self.prompt_type.is_none() {
             Position {
                 col: self.command_bar.caret_col(),
                 row: bottom_row,
             }
+        } else if let Some(hex_view) = &self.hex_view {
+            hex_view.caret_position()
+        } else {
+            self.view.caret_position()
         };
         debug_assert!(caret_position.col < self.terminal_size.width);
         debug_assert!(caret_position.row < self.terminal_size.height);
 
         let _ = Terminal::move_caret_to(caret_position);
-        let _ = Terminal::show_caret();
-        let _ = Terminal::execute();
+        if self.pager.is_none() {
+            let _ = Terminal::show_caret();
+        }
+        let _ = Terminal::present();
     }
 }
 
@@ -312,7 +964,407 @@ impl Drop for Editor {
     fn drop(&mut self) {
         let _ = Terminal::terminate();
         if self.should_quit {
+            // the unsaved changes were discarded on purpose
+            if let Some(swap_file) = &mut self.swap_file {
+                swap_file.remove();
+            }
             let _ = Terminal::print("Goodbye!\r\n");
+            let _ = Terminal::execute();
+            if self.to_stdout {
+                let contents = self.view.buffer.contents();
+                let encoding = self.view.buffer.file_info.get_encoding();
+                // write it in the encoding it was read in, or as UTF-8 rather than not at all
+                let bytes = encoding.encode(&contents).unwrap_or_else(|err| {
+                    let _ = writeln!(stderr(), "Writing UTF-8 instead, as {err}");
+                    contents.into_bytes()
+                });
+                let _ = stdout().write_all(&bytes);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crossterm::event::{KeyCode, KeyModifiers};
+    use event_log::parse_events;
+    use terminal::MemoryBackend;
+
+    fn start_editor(args: &[&str], size: Size) -> (Editor, MemoryBackend) {
+        let backend = MemoryBackend::new(size);
+        Terminal::set_backend(Box::new(backend.clone()));
+        let args = CliArgs::parse(args.iter().map(|arg| String::from(*arg)));
+        (Editor::with_args(&args, None).unwrap(), backend)
+    }
+
+    /// Replays an event log recorded with `--record` against an editor holding `contents`,
+    /// on a terminal of the size the log starts with.
+    fn replay(contents: &str, log: &str) -> (Editor, MemoryBackend) {
+        let events = parse_events(log).unwrap();
+        let Some(&Event::Resize(width, height)) = events.first() else {
+            panic!("The log does not start with the terminal size");
+        };
+        let backend = MemoryBackend::new(Size {
+            width: usize::from(width),
+            height: usize::from(height),
+        });
+        Terminal::set_backend(Box::new(backend.clone()));
+        let stdin_contents = Some(Ok(contents.as_bytes().to_vec()));
+        let mut editor = Editor::with_args(&CliArgs::default(), stdin_contents).unwrap();
+        for event in events {
+            editor.evaluate_event(event);
+            editor.update_status();
+            editor.refresh_screen();
+        }
+        (editor, backend)
+    }
+
+    fn numbered_lines(count: usize) -> String {
+        let lines: Vec<String> = (1..=count).map(|n| format!("line {n}")).collect();
+        format!("{}\n", lines.join("\n"))
+    }
+
+    fn feed(editor: &mut Editor, commands: impl IntoIterator<Item = Command>) {
+        for command in commands {
+            editor.process_command(command);
         }
+        editor.update_status();
+        editor.refresh_screen();
+    }
+
+    fn type_text(text: &str) -> Vec<Command> {
+        text.chars().map(|c| Edit(Insert(c))).collect()
+    }
+
+    #[test]
+    fn test_render_into_memory_backend() {
+        let size = Size {
+            width: 40,
+            height: 6,
+        };
+        let (mut editor, backend) = start_editor(&[], size);
+        feed(&mut editor, []);
+        let rows = backend.rows();
+        assert_eq!(rows.len(), 6);
+        assert!(rows[3].contains("hecto editor"), "{rows:?}");
+        assert!(rows[4].starts_with("[No Name] - 0 lines"), "{rows:?}");
+        assert_eq!(backend.title(), "[No Name] - hecto");
+        assert_eq!(backend.caret(), Some(Position { col: 0, row: 0 }));
+
+        feed(&mut editor, type_text("fn main"));
+        feed(&mut editor, [Edit(InsertNewLine)]);
+        feed(&mut editor, type_text("日本"));
+        let rows = backend.rows();
+        assert_eq!(rows[..3], ["fn main", "日本", "~"]);
+        assert!(
+            rows[4].starts_with("[No Name](modified) - 2 lines"),
+            "{rows:?}"
+        );
+        // the wide characters take two columns each
+        assert_eq!(backend.caret(), Some(Position { col: 4, row: 1 }));
+
+        feed(&mut editor, [System(Save)]);
+        assert_eq!(backend.rows()[5], "Save as:");
+        assert_eq!(backend.caret(), Some(Position { col: 9, row: 5 }));
+        feed(&mut editor, [System(Dismiss)]);
+        assert_eq!(backend.rows()[5], "Aborted.");
+        assert_eq!(backend.caret(), Some(Position { col: 4, row: 1 }));
+    }
+
+    #[test]
+    fn test_auto_pairs_leave_lifetimes_alone() {
+        let size = Size {
+            width: 80,
+            height: 6,
+        };
+        let (mut editor, _) = start_editor(&["pairs.rs"], size);
+        let text = "fn f<'a, 'b: 'a>(s: &'a str) -> [char; 2] { ['x', 'y'] }";
+        feed(&mut editor, type_text(text));
+        assert_eq!(editor.view.buffer.contents(), format!("{text}\n"));
+    }
+
+    #[test]
+    fn test_save_as_when_read_only() {
+        let size = Size {
+            width: 40,
+            height: 6,
+        };
+        let (mut editor, backend) = start_editor(&["--readonly"], size);
+        feed(&mut editor, type_text("x"));
+        assert!(
+            backend.rows()[5].contains("read-only"),
+            "{:?}",
+            backend.rows()
+        );
+        feed(&mut editor, [System(SaveAs)]);
+        assert_eq!(editor.prompt_type, PromptType::Save);
+    }
+
+    #[test]
+    fn test_save_refused_after_lossy_decode() {
+        let path = std::env::temp_dir().join(format!("hecto-lossy-{}.txt", std::process::id()));
+        let bytes = b"\xEF\xBB\xBFcaf\xE9\n";
+        fs::write(&path, bytes).unwrap();
+        let size = Size {
+            width: 100,
+            height: 6,
+        };
+        let (mut editor, backend) = start_editor(&[path.to_str().unwrap()], size);
+        assert!(editor.view.buffer.is_read_only());
+        feed(&mut editor, type_text("x"));
+        feed(&mut editor, [System(Save)]);
+        assert!(
+            backend.rows()[5].contains("not valid UTF-8"),
+            "{:?}",
+            backend.rows()
+        );
+        assert_eq!(fs::read(&path).unwrap(), bytes);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_dismiss_recover_keeps_writing_swap_file() {
+        let dir = std::env::temp_dir().join(format!("hecto-recover-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("file.txt");
+        let stale_path = dir.join(".file.txt.swp");
+        fs::write(&path, "saved\n").unwrap();
+        fs::write(&stale_path, "unsaved\n").unwrap();
+        let size = Size {
+            width: 100,
+            height: 6,
+        };
+        let (mut editor, _) = start_editor(&[path.to_str().unwrap()], size);
+        assert_eq!(editor.prompt_type, PromptType::Recover);
+
+        feed(&mut editor, [System(Dismiss)]);
+        feed(&mut editor, type_text("x"));
+        editor.handle_timer(Timer::SwapFile);
+        // the kept swap file is untouched, and the edits went to another one
+        assert_eq!(fs::read_to_string(&stale_path).unwrap(), "unsaved\n");
+        let swap_contents = fs::read_to_string(dir.join(".file.txt.swo")).unwrap();
+        assert!(swap_contents.ends_with("\nxsaved\n"), "{swap_contents:?}");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_reload_asks_before_discarding_changes() {
+        let path = std::env::temp_dir().join(format!("hecto-reload-{}.txt", std::process::id()));
+        fs::write(&path, "saved\n").unwrap();
+        let size = Size {
+            width: 100,
+            height: 6,
+        };
+        let (mut editor, _) = start_editor(&[path.to_str().unwrap()], size);
+        feed(&mut editor, type_text("unsaved "));
+        feed(&mut editor, [System(Reload)]);
+        assert_eq!(editor.prompt_type, PromptType::Reload);
+        feed(&mut editor, [Edit(Insert('n'))]);
+        assert_eq!(editor.view.buffer.contents(), "unsaved saved\n");
+        feed(&mut editor, [System(Reload), Edit(Insert('y'))]);
+        assert!(editor.prompt_type.is_none());
+        assert_eq!(editor.view.buffer.contents(), "saved\n");
+        assert_eq!(editor.view.buffer.dirty, 0);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_hex_view_asks_before_overwriting_changed_file() {
+        let path = std::env::temp_dir().join(format!("hecto-hex-{}.bin", std::process::id()));
+        fs::write(&path, b"\x7fELF\0\0").unwrap();
+        let size = Size {
+            width: 100,
+            height: 6,
+        };
+        let (mut editor, _) = start_editor(&[path.to_str().unwrap()], size);
+        assert!(editor.hex_view.is_some());
+        feed(&mut editor, type_text("ab"));
+
+        fs::write(&path, b"other file\0").unwrap();
+        feed(&mut editor, [System(Save)]);
+        assert_eq!(
+            editor.prompt_type,
+            PromptType::FileChanged { on_save: true }
+        );
+        assert_eq!(fs::read(&path).unwrap(), b"other file\0");
+        feed(&mut editor, [Edit(Insert('k'))]);
+        assert!(editor.prompt_type.is_none());
+        assert_eq!(fs::read(&path).unwrap(), b"\xabELF\0\0");
+
+        feed(&mut editor, type_text("cd"));
+        fs::write(&path, b"changed again\0").unwrap();
+        feed(&mut editor, [System(Save), Edit(Insert('r'))]);
+        assert!(editor.prompt_type.is_none());
+        assert_eq!(editor.hex_view.as_ref().unwrap().dirty, 0);
+        assert_eq!(fs::read(&path).unwrap(), b"changed again\0");
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_dismiss_file_changed() {
+        let dir = std::env::temp_dir().join(format!("hecto-changed-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("file.txt");
+        fs::write(&path, "first\n").unwrap();
+        let size = Size {
+            width: 100,
+            height: 6,
+        };
+        let (mut editor, _) = start_editor(&[path.to_str().unwrap()], size);
+
+        fs::write(&path, "changed\n").unwrap();
+        editor.check_file_changed();
+        assert_eq!(
+            editor.prompt_type,
+            PromptType::FileChanged { on_save: false }
+        );
+        feed(&mut editor, [System(Dismiss)]);
+        assert!(editor.prompt_type.is_none());
+        // not asked again about the same change
+        editor.check_file_changed();
+        assert!(editor.prompt_type.is_none());
+        // but still before overwriting it
+        feed(&mut editor, [System(Save)]);
+        assert_eq!(
+            editor.prompt_type,
+            PromptType::FileChanged { on_save: true }
+        );
+        feed(&mut editor, [System(Dismiss)]);
+        // and about a further change
+        fs::write(&path, "changed again\n").unwrap();
+        editor.check_file_changed();
+        assert_eq!(
+            editor.prompt_type,
+            PromptType::FileChanged { on_save: false }
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_replay_scrolling() {
+        let log = include_str!("../../testdata/replays/scroll.keys");
+        let (editor, backend) = replay(&numbered_lines(100), log);
+        let expected = numbered_lines(100).replace("line 100\n", "line 100!\n");
+        assert_eq!(editor.view.buffer.contents(), expected);
+        let rows = backend.rows();
+        let lines: Vec<String> = (86..=93).map(|n| format!("line {n}")).collect();
+        assert_eq!(rows[..8], lines);
+        assert!(
+            rows[8].starts_with("[No Name](modified) - 100 lines"),
+            "{rows:?}"
+        );
+        assert_eq!(backend.caret(), Some(Position { col: 7, row: 1 }));
+    }
+
+    #[test]
+    fn test_replay_mouse() {
+        let log = include_str!("../../testdata/replays/mouse.keys");
+        let (editor, backend) = replay(&numbered_lines(100), log);
+        let expected = numbered_lines(100).replace("line 9\nline 10\nline 11\n", "linXne 11\n");
+        assert_eq!(editor.view.buffer.contents(), expected);
+        let rows = backend.rows();
+        assert_eq!(
+            rows[..8],
+            [
+                "line 4",
+                "line 5",
+                "line 6",
+                "line 7",
+                "line 8",
+                "linXne 11",
+                "line 12",
+                "line 13"
+            ]
+        );
+        assert_eq!(backend.caret(), Some(Position { col: 4, row: 5 }));
+    }
+
+    #[test]
+    fn test_mouse_clicks() {
+        let size = Size {
+            width: 60,
+            height: 6,
+        };
+        let (mut editor, backend) = start_editor(&[], size);
+        feed(&mut editor, type_text("a\t緑b"));
+        feed(&mut editor, [Edit(InsertNewLine)]);
+        feed(&mut editor, type_text("x"));
+        let click = |col, row| Mouse(Click(Position { col, row }));
+
+        // the wide character covers columns 4 and 5, after the tab
+        feed(&mut editor, [click(5, 0)]);
+        assert_eq!(backend.caret(), Some(Position { col: 4, row: 0 }));
+        feed(&mut editor, [click(30, 1)]);
+        assert_eq!(backend.caret(), Some(Position { col: 1, row: 1 }));
+        feed(&mut editor, [click(30, 3)]);
+        assert_eq!(backend.caret(), Some(Position { col: 0, row: 2 }));
+
+        let status_bar = &backend.rows()[4];
+        let col = status_bar.find("| LF |").unwrap().saturating_add(2);
+        feed(&mut editor, [click(col, 4)]);
+        assert!(
+            backend.rows()[4].contains("| CRLF |"),
+            "{:?}",
+            backend.rows()
+        );
+        assert_eq!(backend.rows()[5], "Converted line endings to CRLF");
+        // the caret stays where it was
+        assert_eq!(backend.caret(), Some(Position { col: 0, row: 2 }));
+    }
+
+    #[test]
+    fn test_enhanced_keyboard_events() {
+        let size = Size {
+            width: 40,
+            height: 6,
+        };
+        let (mut editor, backend) = start_editor(&[], size);
+        let key =
+            |code, modifiers, kind| Event::Key(KeyEvent::new_with_kind(code, modifiers, kind));
+        for kind in [
+            KeyEventKind::Press,
+            KeyEventKind::Release,
+            KeyEventKind::Repeat,
+            KeyEventKind::Release,
+        ] {
+            editor.evaluate_event(key(KeyCode::Char('a'), KeyModifiers::NONE, kind));
+        }
+        editor.evaluate_event(key(KeyCode::Left, KeyModifiers::NONE, KeyEventKind::Press));
+        // Ctrl-Enter starts a new line without splitting the current one
+        editor.evaluate_event(key(
+            KeyCode::Enter,
+            KeyModifiers::CONTROL,
+            KeyEventKind::Press,
+        ));
+        editor.evaluate_event(key(
+            KeyCode::Char('b'),
+            KeyModifiers::NONE,
+            KeyEventKind::Press,
+        ));
+        assert_eq!(editor.view.buffer.contents(), "aa\nb\n");
+
+        let control_shift = KeyModifiers::CONTROL | KeyModifiers::SHIFT;
+        editor.evaluate_event(key(KeyCode::Char('s'), control_shift, KeyEventKind::Press));
+        feed(&mut editor, []);
+        assert_eq!(backend.rows()[5], "Save as:");
+    }
+
+    #[test]
+    fn test_replay_search() {
+        let log = include_str!("../../testdata/replays/search.keys");
+        let (editor, backend) = replay(&numbered_lines(100), log);
+        let expected = numbered_lines(100).replace("line 27\n", "line 2*7\n");
+        assert_eq!(editor.view.buffer.contents(), expected);
+        let rows = backend.rows();
+        assert_eq!(
+            rows[..8],
+            [
+                "line 23", "line 24", "line 25", "line 26", "line 2*7", "line 28", "line 29",
+                "line 30"
+            ]
+        );
+        assert_eq!(rows[9], "");
+        assert_eq!(backend.caret(), Some(Position { col: 7, row: 4 }));
     }
 }
@@ -0,0 +1,33 @@
+pub const DEFAULT_TAB_WIDTH: usize = 4;
+
+// independent options from the command line, not a state machine
+#[allow(clippy::struct_excessive_bools)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Settings {
+    // number of columns between two tab stops
+    pub tab_width: usize,
+    // insert spaces instead of a tab character when Tab is pressed
+    pub expand_tabs: bool,
+    // keep the previous contents as `file~` when saving
+    pub backup: bool,
+    // reject all edits, to view a file without changing it by accident
+    pub read_only: bool,
+    // scroll with terminal scroll regions instead of redrawing all rows
+    pub scroll_regions: bool,
+    // capture the mouse to place the caret, select and scroll,
+    // instead of leaving it to the terminal to select text
+    pub mouse: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            tab_width: DEFAULT_TAB_WIDTH,
+            expand_tabs: false,
+            backup: false,
+            read_only: false,
+            scroll_regions: true,
+            mouse: true,
+        }
+    }
+}
@@ -0,0 +1,325 @@
+use super::atomic_write::write_private;
+use std::env::var_os;
+use std::ffi::OsString;
+use std::fs::{create_dir_all, read_to_string, remove_file};
+use std::io::Error;
+use std::path::{Path, PathBuf};
+use std::process::{self, Command, Stdio};
+
+const HEADER_PREFIX: &str = "hecto swap ";
+// the second one is used while the first belongs to another session
+const EXTENSIONS: [&str; 2] = ["swp", "swo"];
+
+/// A copy of the unsaved contents of a buffer, written periodically,
+/// so that the edits survive a crash or a lost session.
+/// It lives next to the original as `.name.swp`, or `.name.swo` if that one belongs
+/// to another session, or in the cache directory if the directory of the original is not writable.
+/// The first line records the process and the host of the session owning it,
+/// so that a swap file is only considered stale once that process is gone.
+/// Only the owner of the original can read it, as it holds the same text.
+pub struct SwapFile {
+    original: PathBuf,
+    // where the swap file has been written to, if it has
+    written_to: Option<PathBuf>,
+    // the `dirty` counter of the buffer at the time of the last write
+    written_dirty: Option<usize>,
+    // stale swap files which the user chose to keep, so they must not be overwritten
+    left_alone: Vec<PathBuf>,
+}
+
+/// A swap file left behind by another session.
+#[derive(Debug, PartialEq)]
+pub struct StaleSwapFile {
+    pub path: PathBuf,
+    pub contents: String,
+    // the host of the session, if it runs elsewhere and may still be using the swap file
+    pub other_host: Option<String>,
+}
+
+impl SwapFile {
+    pub fn new(original: &Path) -> Self {
+        Self {
+            original: original.to_path_buf(),
+            written_to: None,
+            written_dirty: None,
+            left_alone: Vec::new(),
+        }
+    }
+    pub fn is_for(&self, original: &Path) -> bool {
+        self.original == original
+    }
+    /// Writes the contents, unless they have already been written at the same `dirty` count.
+    pub fn update<F>(&mut self, dirty: usize, contents: F) -> Result<(), Error>
+    where
+        F: FnOnce() -> String,
+    {
+        if self.written_dirty == Some(dirty) {
+            return Ok(());
+        }
+        let mut swap_contents = Owner::current().to_header();
+        swap_contents.push_str(&contents());
+        let mut result = Err(Error::other("No location for the swap file"));
+        for path in self.candidates() {
+            let taken_over = self.written_to.as_ref() == Some(&path);
+            if self.left_alone.contains(&path)
+                || (!taken_over
+                    && read_swap_file(&path).is_some_and(|(owner, _)| {
+                        owner.is_some_and(|owner| owner.may_be_running())
+                    }))
+            {
+                continue;
+            }
+            if let Some(dir) = cache_dir().filter(|dir| path.starts_with(dir)) {
+                let _ = create_dir_all(dir);
+            }
+            result = write_private(&path, swap_contents.as_bytes());
+            if result.is_ok() {
+                if self
+                    .written_to
+                    .as_ref()
+                    .is_some_and(|written| *written != path)
+                {
+                    self.remove();
+                }
+                self.written_to = Some(path);
+                self.written_dirty = Some(dirty);
+                break;
+            }
+        }
+        result
+    }
+    /// Takes over a stale swap file whose contents have been recovered,
+    /// so that it is cleaned up like one written by this session.
+    pub fn take_over(&mut self, path: PathBuf) {
+        self.written_to = Some(path);
+        self.written_dirty = None;
+    }
+    /// Keeps a stale swap file as it is, writing the swap file of this session elsewhere.
+    pub fn leave_alone(&mut self, path: PathBuf) {
+        self.left_alone.push(path);
+    }
+    /// Removes the swap file, e.g. after the buffer has been saved.
+    pub fn remove(&mut self) {
+        if let Some(path) = self.written_to.take() {
+            let _ = remove_file(path);
+        }
+        self.written_dirty = None;
+    }
+    /// Returns a swap file left behind by a session which has ended,
+    /// or by one on another host, which can't be checked.
+    pub fn find_stale(&self) -> Option<StaleSwapFile> {
+        self.candidates().into_iter().find_map(|path| {
+            let (owner, contents) = read_swap_file(&path)?;
+            match owner {
+                Some(owner) if owner.is_on_other_host() => Some(StaleSwapFile {
+                    path,
+                    contents,
+                    other_host: Some(owner.host),
+                }),
+                Some(owner) if owner.may_be_running() || owner.is_current() => None,
+                _ => Some(StaleSwapFile {
+                    path,
+                    contents,
+                    other_host: None,
+                }),
+            }
+        })
+    }
+    /// Whether another running session on this host has a swap file for the same file.
+    pub fn is_in_use(&self) -> bool {
+        self.candidates().iter().any(|path| {
+            read_swap_file(path).is_some_and(|(owner, _)| {
+                owner.is_some_and(|owner| !owner.is_on_other_host() && owner.may_be_running())
+            })
+        })
+    }
+    fn candidates(&self) -> Vec<PathBuf> {
+        let mut result = Vec::new();
+        for extension in EXTENSIONS {
+            let mut name = OsString::from(".");
+            name.push(self.original.file_name().unwrap_or_default());
+            name.push(format!(".{extension}"));
+            result.push(self.original.with_file_name(name));
+        }
+        if let Some(cache_dir) = cache_dir() {
+            // encode the full path, so that files of the same name don't collide
+            let absolute = std::path::absolute(&self.original).unwrap_or_default();
+            let name = absolute.to_string_lossy().replace(['/', '\\'], "%");
+            for extension in EXTENSIONS {
+                result.push(cache_dir.join(format!("{name}.{extension}")));
+            }
+        }
+        result
+    }
+}
+
+/// The session which wrote a swap file.
+#[derive(Debug, PartialEq)]
+struct Owner {
+    pid: u32,
+    host: String,
+}
+
+impl Owner {
+    fn current() -> Self {
+        Self {
+            pid: process::id(),
+            host: host_name(),
+        }
+    }
+    fn to_header(&self) -> String {
+        format!("{HEADER_PREFIX}{} {}\n", self.pid, self.host)
+    }
+    fn from_header(line: &str) -> Option<Self> {
+        let (pid, host) = line.strip_prefix(HEADER_PREFIX)?.split_once(' ')?;
+        Some(Self {
+            pid: pid.parse().ok()?,
+            host: host.to_string(),
+        })
+    }
+    fn is_current(&self) -> bool {
+        *self == Self::current()
+    }
+    fn is_on_other_host(&self) -> bool {
+        self.host != host_name()
+    }
+    /// Whether this is another session which has not ended yet, as far as can be told:
+    /// a process on another host can't be checked, so it may still be running.
+    fn may_be_running(&self) -> bool {
+        !self.is_current() && (self.is_on_other_host() || is_process_running(self.pid))
+    }
+}
+
+/// Reads a swap file, returning its owner and the contents without the header.
+/// Swap files without a header have no known owner, which is treated like one that has ended.
+fn read_swap_file(path: &Path) -> Option<(Option<Owner>, String)> {
+    let contents = read_to_string(path).ok()?;
+    if let Some((owner, rest)) = contents
+        .split_once('\n')
+        .and_then(|(header, rest)| Owner::from_header(header).map(|owner| (owner, rest)))
+    {
+        return Some((Some(owner), rest.to_string()));
+    }
+    Some((None, contents))
+}
+
+fn is_process_running(pid: u32) -> bool {
+    if pid == 0 {
+        return false;
+    }
+    let proc_dir = Path::new("/proc");
+    if proc_dir.join("self").exists() {
+        return proc_dir.join(pid.to_string()).exists();
+    }
+    Command::new("kill")
+        .args(["-0", &pid.to_string()])
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success())
+}
+
+fn host_name() -> String {
+    if let Some(name) = read_to_string("/etc/hostname")
+        .ok()
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+    {
+        return name;
+    }
+    Command::new("hostname")
+        .stderr(Stdio::null())
+        .output()
+        .ok()
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+        .unwrap_or_default()
+}
+
+/// The directory for swap files which can't be written next to the original.
+/// It is only created when a swap file is written there.
+fn cache_dir() -> Option<PathBuf> {
+    let dir = var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))?;
+    Some(dir.join("hecto"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_swap_file() {
+        let dir = std::env::temp_dir().join(format!("hecto-swap-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let original = dir.join("file.txt");
+        let swap_path = dir.join(".file.txt.swp");
+
+        let mut swap_file = SwapFile::new(&original);
+        assert!(swap_file.find_stale().is_none());
+        swap_file.update(1, || String::from("first")).unwrap();
+        let header = Owner::current().to_header();
+        assert_eq!(
+            read_to_string(&swap_path).unwrap(),
+            format!("{header}first")
+        );
+        // not written again for the same dirty count
+        swap_file.update(1, || String::from("second")).unwrap();
+        assert_eq!(
+            read_to_string(&swap_path).unwrap(),
+            format!("{header}first")
+        );
+
+        // the owner is still running
+        let mut other_session = SwapFile::new(&original);
+        assert!(other_session.find_stale().is_none());
+        assert!(!other_session.is_in_use());
+        let mut child = Command::new("sleep").arg("10").spawn().unwrap();
+        let running = Owner {
+            pid: child.id(),
+            host: host_name(),
+        };
+        std::fs::write(&swap_path, format!("{}first", running.to_header())).unwrap();
+        assert!(other_session.find_stale().is_none());
+        assert!(other_session.is_in_use());
+        child.kill().unwrap();
+        child.wait().unwrap();
+
+        let stale = |other_host: Option<&str>| StaleSwapFile {
+            path: swap_path.clone(),
+            contents: String::from("first"),
+            other_host: other_host.map(String::from),
+        };
+        // the owner is gone
+        let gone = Owner {
+            pid: 0,
+            host: host_name(),
+        };
+        std::fs::write(&swap_path, format!("{}first", gone.to_header())).unwrap();
+        assert_eq!(other_session.find_stale(), Some(stale(None)));
+        // a swap file without a header has no known owner
+        std::fs::write(&swap_path, "first").unwrap();
+        assert_eq!(other_session.find_stale(), Some(stale(None)));
+
+        // the owner on another host may still be running, so its swap file is not replaced
+        let elsewhere = Owner {
+            pid: 1,
+            host: String::from("elsewhere"),
+        };
+        std::fs::write(&swap_path, format!("{}first", elsewhere.to_header())).unwrap();
+        assert_eq!(other_session.find_stale(), Some(stale(Some("elsewhere"))));
+        other_session.update(1, || String::from("second")).unwrap();
+        assert!(read_to_string(&swap_path).unwrap().ends_with("\nfirst"));
+        let other_path = dir.join(".file.txt.swo");
+        assert!(read_to_string(&other_path).unwrap().ends_with("\nsecond"));
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&other_path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+        other_session.remove();
+        swap_file.remove();
+        assert!(!swap_path.exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -38,7 +38,7 @@ impl From<AnnotationType> for Attribute {
                 foreground: Some(HexColor::from("#D2B900").unwrap().to_color()),
                 background: None,
             },
-            AnnotationType::LifetimeSpecifier=> Attribute {
+            AnnotationType::LifetimeSpecifier => Attribute {
                 foreground: Some(HexColor::from("#66CDE2").unwrap().to_color()),
                 background: None,
             },
@@ -50,6 +50,30 @@ impl From<AnnotationType> for Attribute {
                 foreground: Some(HexColor::from("#FFB366").unwrap().to_color()),
                 background: None,
             },
+            AnnotationType::MatchingBracket => Attribute {
+                foreground: Some(Color::White),
+                background: Some(HexColor::from("#5F87AF").unwrap().to_color()),
+            },
+            AnnotationType::Selection => Attribute {
+                foreground: Some(Color::White),
+                background: Some(HexColor::from("#3A5A8C").unwrap().to_color()),
+            },
+            AnnotationType::Warning => Attribute {
+                foreground: Some(HexColor::from("#FFD75F").unwrap().to_color()),
+                background: None,
+            },
+            AnnotationType::Error => Attribute {
+                foreground: Some(Color::White),
+                background: Some(HexColor::from("#AF0000").unwrap().to_color()),
+            },
+            AnnotationType::Added => Attribute {
+                foreground: Some(HexColor::from("#5FD75F").unwrap().to_color()),
+                background: None,
+            },
+            AnnotationType::Removed => Attribute {
+                foreground: Some(HexColor::from("#FF5F5F").unwrap().to_color()),
+                background: None,
+            },
         }
     }
 }
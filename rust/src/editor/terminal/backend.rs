@@ -0,0 +1,20 @@
+use super::screen::Screen;
+use crate::prelude::Size;
+use std::io::Error;
+
+/// Where `Terminal` sends its output: a real terminal, or a grid in memory for tests.
+pub trait Backend {
+    /// Prepares the terminal for drawing, e.g. by switching to the alternate screen.
+    fn initialize(&mut self) -> Result<(), Error>;
+    /// Restores the terminal to its state before `initialize`.
+    fn terminate(&mut self) -> Result<(), Error>;
+    fn size(&self) -> Result<Size, Error>;
+    fn set_title(&mut self, title: &str) -> Result<(), Error>;
+    /// Has the terminal report mouse events, until `terminate`.
+    fn enable_mouse_capture(&mut self) -> Result<(), Error>;
+    /// Shows what changed on `screen` since the last call, and places the caret.
+    fn present(&mut self, screen: &mut Screen) -> Result<(), Error>;
+    /// Prints `string` as it is, e.g. after `terminate`.
+    fn print(&mut self, string: &str) -> Result<(), Error>;
+    fn flush(&mut self) -> Result<(), Error>;
+}
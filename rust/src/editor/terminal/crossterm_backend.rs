@@ -0,0 +1,126 @@
+use super::backend::Backend;
+use super::screen::Screen;
+use crate::prelude::Size;
+use crossterm::cursor::Show;
+use crossterm::event::{
+    DisableMouseCapture, EnableMouseCapture, KeyboardEnhancementFlags, PopKeyboardEnhancementFlags,
+    PushKeyboardEnhancementFlags,
+};
+use crossterm::style::Print;
+use crossterm::terminal::{
+    disable_raw_mode, enable_raw_mode, size, supports_keyboard_enhancement, Clear, ClearType,
+    DisableLineWrap, EnableLineWrap, EnterAlternateScreen, LeaveAlternateScreen, SetTitle,
+};
+use crossterm::{Command, QueueableCommand};
+use std::fs::{File, OpenOptions};
+use std::io::{stdout, BufWriter, Error, IsTerminal, Write};
+use std::sync::{Mutex, OnceLock};
+
+// The terminal to draw on if stdout is not one, e.g. when the buffer is written to stdout.
+// None if stdout is a terminal, or if there is no terminal at all.
+static TTY_OUTPUT: OnceLock<Option<Mutex<BufWriter<File>>>> = OnceLock::new();
+
+/// Draws on the real terminal with crossterm.
+#[derive(Default)]
+pub struct CrosstermBackend {
+    // whether the keyboard enhancement flags were pushed, to pop them again
+    keyboard_enhanced: bool,
+}
+
+impl CrosstermBackend {
+    fn queue_command<T: Command>(command: T) -> Result<(), Error> {
+        Self::with_output(|output| {
+            output.queue(command)?;
+            Ok(())
+        })
+    }
+    fn with_output<F>(write: F) -> Result<(), Error>
+    where
+        F: FnOnce(&mut dyn Write) -> Result<(), Error>,
+    {
+        let tty = TTY_OUTPUT.get_or_init(|| {
+            if stdout().is_terminal() {
+                return None;
+            }
+            OpenOptions::new()
+                .write(true)
+                .open("/dev/tty")
+                .ok()
+                .map(|file| Mutex::new(BufWriter::new(file)))
+        });
+        match tty {
+            Some(tty) => {
+                let mut tty = tty
+                    .lock()
+                    .map_err(|_| Error::other("Terminal output is poisoned"))?;
+                write(&mut *tty)
+            }
+            None => write(&mut stdout()),
+        }
+    }
+}
+
+impl Backend for CrosstermBackend {
+    fn initialize(&mut self) -> Result<(), Error> {
+        enable_raw_mode()?;
+        Self::queue_command(EnterAlternateScreen)?;
+        // Opt into the kitty keyboard protocol where supported, which reports chords like
+        // Ctrl-Shift-S or Ctrl-Enter, tells Tab from Ctrl-I, and Esc without a delay.
+        // The flags are kept per screen, so they are pushed on the alternate one.
+        // crossterm sends its query to stdout if it cannot write to /dev/tty, so the probe is
+        // skipped when stdout is redirected, where nothing would answer it either.
+        if stdout().is_terminal() && supports_keyboard_enhancement().unwrap_or(false) {
+            Self::queue_command(PushKeyboardEnhancementFlags(
+                KeyboardEnhancementFlags::DISAMBIGUATE_ESCAPE_CODES
+                    | KeyboardEnhancementFlags::REPORT_EVENT_TYPES,
+            ))?;
+            self.keyboard_enhanced = true;
+        }
+        Self::queue_command(DisableLineWrap)?;
+        Self::queue_command(Clear(ClearType::All))?;
+        self.flush()
+    }
+    fn terminate(&mut self) -> Result<(), Error> {
+        Self::queue_command(DisableMouseCapture)?;
+        if self.keyboard_enhanced {
+            Self::queue_command(PopKeyboardEnhancementFlags)?;
+            self.keyboard_enhanced = false;
+        }
+        Self::queue_command(LeaveAlternateScreen)?;
+        Self::queue_command(EnableLineWrap)?;
+        Self::queue_command(Show)?;
+        self.flush()?;
+        disable_raw_mode()?;
+        Ok(())
+    }
+    /// Edge Case for systems with `usize` < `u16`
+    /// * A `Size` representing the terminal size. Any coordinate `z` truncated with `usize` < `z`
+    ///   < `u16`
+    fn size(&self) -> Result<Size, Error> {
+        let (width16, height16) = size()?;
+        #[allow(clippy::as_conversions)]
+        let width = width16 as usize;
+        #[allow(clippy::as_conversions)]
+        let height = height16 as usize;
+        Ok(Size { width, height })
+    }
+    fn set_title(&mut self, title: &str) -> Result<(), Error> {
+        Self::queue_command(SetTitle(title))
+    }
+    fn enable_mouse_capture(&mut self) -> Result<(), Error> {
+        Self::queue_command(EnableMouseCapture)?;
+        self.flush()
+    }
+    fn present(&mut self, screen: &mut Screen) -> Result<(), Error> {
+        Self::with_output(|output| {
+            screen.present(output)?;
+            output.flush()
+        })
+    }
+    fn print(&mut self, string: &str) -> Result<(), Error> {
+        Self::queue_command(Print(string))
+    }
+    fn flush(&mut self) -> Result<(), Error> {
+        Self::with_output(|output| output.flush())
+    }
+}
@@ -0,0 +1,78 @@
+use super::backend::Backend;
+use super::screen::Screen;
+use crate::prelude::{Position, Size};
+use std::cell::RefCell;
+use std::io::{sink, Error};
+use std::rc::Rc;
+
+#[derive(Default)]
+struct Grid {
+    size: Size,
+    rows: Vec<String>,
+    // None while the caret is hidden
+    caret: Option<Position>,
+    title: String,
+}
+
+/// Keeps the presented screen in memory, so that tests can check what would be shown.
+/// Clones share the same grid, so a test can keep one while `Terminal` uses the other.
+#[derive(Clone, Default)]
+pub struct MemoryBackend {
+    grid: Rc<RefCell<Grid>>,
+}
+
+impl MemoryBackend {
+    pub fn new(size: Size) -> Self {
+        let backend = Self::default();
+        backend.grid.borrow_mut().size = size;
+        backend
+    }
+    /// Returns the text of each row, without trailing blanks.
+    pub fn rows(&self) -> Vec<String> {
+        self.grid
+            .borrow()
+            .rows
+            .iter()
+            .map(|row| String::from(row.trim_end()))
+            .collect()
+    }
+    pub fn caret(&self) -> Option<Position> {
+        self.grid.borrow().caret
+    }
+    pub fn title(&self) -> String {
+        self.grid.borrow().title.clone()
+    }
+}
+
+impl Backend for MemoryBackend {
+    fn initialize(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+    fn terminate(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+    fn size(&self) -> Result<Size, Error> {
+        Ok(self.grid.borrow().size)
+    }
+    fn set_title(&mut self, title: &str) -> Result<(), Error> {
+        self.grid.borrow_mut().title = String::from(title);
+        Ok(())
+    }
+    fn enable_mouse_capture(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+    fn present(&mut self, screen: &mut Screen) -> Result<(), Error> {
+        // the escape sequences are not needed, only the screen they lead to
+        screen.present(&mut sink())?;
+        let mut grid = self.grid.borrow_mut();
+        grid.rows = screen.presented_rows();
+        grid.caret = screen.presented_caret();
+        Ok(())
+    }
+    fn print(&mut self, _string: &str) -> Result<(), Error> {
+        Ok(())
+    }
+    fn flush(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+}
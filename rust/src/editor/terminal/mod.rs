@@ -2,18 +2,26 @@ use crate::prelude::{Position, RowIdx, Size};
 
 use super::AnnotatedString;
 use attribute::Attribute;
-use crossterm::cursor::{Hide, MoveTo, Show};
-use crossterm::style::{
-    Attribute::{Reset, Reverse},
-    Print, ResetColor, SetBackgroundColor, SetForegroundColor,
-};
-use crossterm::terminal::{
-    disable_raw_mode, enable_raw_mode, size, Clear, ClearType, DisableLineWrap, EnableLineWrap,
-    EnterAlternateScreen, LeaveAlternateScreen, SetTitle,
-};
-use crossterm::{queue, Command};
-use std::io::{stdout, Error, Write};
+use backend::Backend;
+use crossterm_backend::CrosstermBackend;
+use screen::{Screen, Scroll, Style};
+use std::cell::RefCell;
+use std::io::Error;
+use std::ops::Range;
 mod attribute;
+mod backend;
+mod crossterm_backend;
+#[cfg(test)]
+mod memory_backend;
+#[cfg(test)]
+pub use memory_backend::MemoryBackend;
+mod screen;
+
+thread_local! {
+    // What is on the terminal and what is going to be, see `Terminal::present`.
+    static SCREEN: RefCell<Screen> = const { RefCell::new(Screen::new()) };
+    static BACKEND: RefCell<Box<dyn Backend>> = RefCell::new(Box::new(CrosstermBackend::default()));
+}
 
 /// Represents the Terminal.
 /// Edge Case for platforms where `usize` < `u16`:
@@ -21,135 +29,112 @@ mod attribute;
 /// `usize::MAX` or `u16::size` rows / colmuns, whichever is smaler.
 /// Each size returned truncates to min(`usize::MAX`, `u16::size`)
 /// And should you attempt to set the caret out of those bounds, it will also be truncated.
+/// Rows and the caret are drawn into a back buffer first, and `present` sends only what changed
+/// to the backend, which is the real terminal unless replaced with `set_backend`.
 pub struct Terminal;
 
 impl Terminal {
     pub fn terminate() -> Result<(), Error> {
-        Self::leave_alternate_screen()?;
-        Self::enable_line_wrap()?;
-        Self::show_caret()?;
-        Self::execute()?;
-        disable_raw_mode()?;
-        Ok(())
+        Self::with_backend(|backend| backend.terminate())
     }
     pub fn initialize() -> Result<(), Error> {
-        enable_raw_mode()?;
-        Self::enter_alternate_screen()?;
-        Self::disable_line_wrap()?;
-        Self::clear_screen()?;
-        Self::execute()?;
-        Ok(())
-    }
-    pub fn enter_alternate_screen() -> Result<(), Error> {
-        Self::queue_command(EnterAlternateScreen)?;
-        Ok(())
-    }
-    pub fn leave_alternate_screen() -> Result<(), Error> {
-        Self::queue_command(LeaveAlternateScreen)?;
-        Ok(())
-    }
-    pub fn clear_screen() -> Result<(), Error> {
-        Self::queue_command(Clear(ClearType::All))?;
-        Ok(())
-    }
-    pub fn clear_line() -> Result<(), Error> {
-        Self::queue_command(Clear(ClearType::UntilNewLine))?;
-        Ok(())
+        Self::with_backend(|backend| backend.initialize())
     }
-    pub fn disable_line_wrap() -> Result<(), Error> {
-        Self::queue_command(DisableLineWrap)?;
-        Ok(())
+    /// Replaces the backend of the current thread, e.g. with a `MemoryBackend` in tests.
+    #[cfg(test)]
+    pub fn set_backend(backend: Box<dyn Backend>) {
+        BACKEND.with_borrow_mut(|current| *current = backend);
+        SCREEN.with_borrow_mut(|screen| *screen = Screen::new());
     }
-    pub fn enable_line_wrap() -> Result<(), Error> {
-        Self::queue_command(EnableLineWrap)?;
-        Ok(())
+    pub fn enable_mouse_capture() -> Result<(), Error> {
+        Self::with_backend(|backend| backend.enable_mouse_capture())
     }
     pub fn set_title(title: &str) -> Result<(), Error> {
-        Self::queue_command(SetTitle(title))?;
-        Ok(())
+        Self::with_backend(|backend| backend.set_title(title))
     }
-    /// Moves the caret to the given Position.
+    /// Sets the size of the back buffer, clearing it if the size changed.
+    pub fn resize(size: Size) -> Result<(), Error> {
+        Self::with_screen(|screen| screen.resize(size))
+    }
+    /// Moves the caret to the given Position once the screen is presented.
     /// # Arguments
     /// * `Position` - the `Poisition` to move the caret to. Will be truncated to `u16::MAX` if
     ///   bitter.
     pub fn move_caret_to(position: Position) -> Result<(), Error> {
-        #[allow(clippy::as_conversions, clippy::cast_possible_truncation)]
-        Self::queue_command(MoveTo(position.col as u16, position.row as u16))?;
-        Ok(())
+        Self::with_screen(|screen| screen.move_caret_to(position))
     }
     pub fn hide_caret() -> Result<(), Error> {
-        Self::queue_command(Hide)?;
-        Ok(())
+        Self::with_screen(|screen| screen.set_caret_visible(false))
     }
     pub fn show_caret() -> Result<(), Error> {
-        Self::queue_command(Show)?;
-        Ok(())
+        Self::with_screen(|screen| screen.set_caret_visible(true))
+    }
+    /// Moves the contents of the given rows up by `count` rows on the terminal itself,
+    /// so that only the rows scrolled into view have to be drawn.
+    pub fn scroll_rows_up(rows: Range<RowIdx>, count: usize) -> Result<(), Error> {
+        Self::with_screen(|screen| screen.scroll_rows(rows, Scroll::Up(count)))
+    }
+    /// Moves the contents of the given rows down by `count` rows, see `scroll_rows_up`.
+    pub fn scroll_rows_down(rows: Range<RowIdx>, count: usize) -> Result<(), Error> {
+        Self::with_screen(|screen| screen.scroll_rows(rows, Scroll::Down(count)))
     }
     pub fn print(string: &str) -> Result<(), Error> {
-        Self::queue_command(Print(string))?;
-        Ok(())
+        Self::with_backend(|backend| backend.print(string))
     }
     pub fn print_row(row: RowIdx, line_text: &str) -> Result<(), Error> {
-        Self::move_caret_to(Position { col: 0, row })?;
-        Self::clear_line()?;
-        Self::print(line_text)?;
-        Ok(())
+        Self::with_screen(|screen| screen.set_row(row, [(line_text, Style::default())]))
     }
     pub fn print_annotated_row(
         row: RowIdx,
         annotated_string: &AnnotatedString,
     ) -> Result<(), Error> {
-        Self::move_caret_to(Position { col: 0, row })?;
-        Self::clear_line()?;
-        annotated_string
-            .into_iter()
-            .try_for_each(|part| -> Result<(), Error> {
-                if let Some(annotation_type) = part.annotation_type {
-                    Self::set_attribute(&annotation_type.into())?;
-                }
-                Self::print(part.string)?;
-                Self::reset_color()?;
-                Ok(())
-            })?;
-        Ok(())
-    }
-    pub fn set_attribute(attribute: &Attribute) -> Result<(), Error> {
-        if let Some(foreground_color) = attribute.foreground {
-            Self::queue_command(SetForegroundColor(foreground_color))?;
-        }
-        if let Some(background_color) = attribute.background {
-            Self::queue_command(SetBackgroundColor(background_color))?;
-        }
-        Ok(())
-    }
-    pub fn reset_color() -> Result<(), Error> {
-        Self::queue_command(ResetColor)?;
-        Ok(())
+        let parts = annotated_string.into_iter().map(|part| {
+            let style = part
+                .annotation_type
+                .map(|annotation_type| Style::from(&Attribute::from(annotation_type)))
+                .unwrap_or_default();
+            (part.string, style)
+        });
+        Self::with_screen(|screen| screen.set_row(row, parts))
     }
     pub fn print_invert_row(row: RowIdx, line_text: &str) -> Result<(), Error> {
-        let width = Self::size()?.width;
-        // :width$ -> pad to width
-        // .width$ -> truncate to width
-        Self::print_row(row, &format!("{Reverse}{line_text:width$.width$}{Reset}"))
+        let style = Style {
+            reverse: true,
+            ..Style::default()
+        };
+        Self::with_screen(|screen| {
+            let width = screen.size().width;
+            // :width$ -> pad to width, so that the whole row is inverted
+            screen.set_row(row, [(format!("{line_text:width$}").as_str(), style)]);
+        })
     }
     /// Returns the current size of this Terminal.
-    /// Edge Case for systems with `usize` < `u16`
-    /// * A `Size` representing the terminal size. Any coordinate `z` truncated with `usize` < `z`
-    ///   < `u16`
     pub fn size() -> Result<Size, Error> {
-        let (width16, height16) = size()?;
-        #[allow(clippy::as_conversions)]
-        let width = width16 as usize;
-        #[allow(clippy::as_conversions)]
-        let height = height16 as usize;
-        Ok(Size { width, height })
+        Self::with_backend(|backend| backend.size())
     }
-    pub fn execute() -> Result<(), Error> {
-        stdout().flush()?;
-        Ok(())
+    /// Sends the rows which changed since the last call, places the caret and flushes.
+    pub fn present() -> Result<(), Error> {
+        Self::with_screen(|screen| Self::with_backend(|backend| backend.present(screen)))?
     }
-    fn queue_command<T: Command>(command: T) -> Result<(), Error> {
-        queue!(stdout(), command)?;
-        Ok(())
+    pub fn execute() -> Result<(), Error> {
+        Self::with_backend(|backend| backend.flush())
+    }
+    /// Fails instead of panicking if the screen is gone, e.g. while the thread exits.
+    fn with_screen<T, F: FnOnce(&mut Screen) -> T>(f: F) -> Result<T, Error> {
+        SCREEN
+            .try_with(|screen| screen.try_borrow_mut().map(|mut screen| f(&mut screen)))
+            .map_err(Error::other)?
+            .map_err(Error::other)
+    }
+    fn with_backend<T, F>(f: F) -> Result<T, Error>
+    where
+        F: FnOnce(&mut dyn Backend) -> Result<T, Error>,
+    {
+        BACKEND
+            .try_with(|backend| {
+                let mut backend = backend.try_borrow_mut().map_err(Error::other)?;
+                f(backend.as_mut())
+            })
+            .map_err(Error::other)?
     }
 }
@@ -0,0 +1,415 @@
+use super::attribute::Attribute;
+use crate::prelude::{ColIdx, Position, RowIdx, Size};
+use crossterm::cursor::{Hide, MoveTo, Show};
+use crossterm::style::{
+    Attribute::{NoReverse, Reset, Reverse},
+    Color, Print, SetAttribute, SetBackgroundColor, SetForegroundColor,
+};
+use crossterm::terminal::{Clear, ClearType, ScrollDown, ScrollUp};
+use crossterm::{Command, QueueableCommand};
+use std::fmt;
+use std::io::{Error, Write};
+use std::ops::Range;
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+// Reprinting a few unchanged cells is cheaper than moving the caret over them.
+const MAX_REPRINTED_GAP: usize = 4;
+
+#[derive(Clone, Copy, Default, Debug, Eq, PartialEq)]
+pub struct Style {
+    pub foreground: Option<Color>,
+    pub background: Option<Color>,
+    pub reverse: bool,
+}
+
+impl From<&Attribute> for Style {
+    fn from(attribute: &Attribute) -> Self {
+        Self {
+            foreground: attribute.foreground,
+            background: attribute.background,
+            reverse: false,
+        }
+    }
+}
+
+/// Moves the contents of some rows, with the rows scrolled into view left blank.
+#[derive(Copy, Clone, Debug)]
+pub enum Scroll {
+    // the contents move up by this many rows
+    Up(usize),
+    // the contents move down by this many rows
+    Down(usize),
+}
+
+/// Limits scrolling to the given rows (DECSTBM), or lifts the limit if `None`.
+/// This also moves the caret to the top left corner.
+struct SetScrollRegion(Option<Range<RowIdx>>);
+
+impl Command for SetScrollRegion {
+    fn write_ansi(&self, f: &mut impl fmt::Write) -> fmt::Result {
+        match &self.0 {
+            Some(rows) => write!(f, "\x1b[{};{}r", rows.start.saturating_add(1), rows.end),
+            None => write!(f, "\x1b[r"),
+        }
+    }
+    #[cfg(windows)]
+    fn execute_winapi(&self) -> Result<(), Error> {
+        Err(Error::other(
+            "scroll regions need a terminal with ANSI support",
+        ))
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct Cell {
+    // empty for the right half of a wide grapheme
+    grapheme: String,
+    style: Style,
+}
+
+impl Default for Cell {
+    fn default() -> Self {
+        Self {
+            grapheme: String::from(" "),
+            style: Style::default(),
+        }
+    }
+}
+
+impl Cell {
+    fn is_continuation(&self) -> bool {
+        self.grapheme.is_empty()
+    }
+    fn is_blank(&self) -> bool {
+        self.grapheme == " " && self.style == Style::default()
+    }
+}
+
+/// The cells the terminal shows and the cells it should show after the next `present`,
+/// so that only the cells which changed in between have to be sent.
+pub struct Screen {
+    size: Size,
+    // None if the contents of the terminal are unknown, e.g. before the first frame
+    front: Option<Vec<Vec<Cell>>>,
+    back: Vec<Vec<Cell>>,
+    caret: Position,
+    caret_visible: bool,
+    // the caret as last sent to the terminal
+    front_caret: Option<(Position, bool)>,
+    // scrolling to do on the terminal before sending the changed cells
+    pending_scrolls: Vec<(Range<RowIdx>, Scroll)>,
+}
+
+impl Screen {
+    pub const fn new() -> Self {
+        Self {
+            size: Size {
+                width: 0,
+                height: 0,
+            },
+            front: None,
+            back: Vec::new(),
+            caret: Position { col: 0, row: 0 },
+            caret_visible: false,
+            front_caret: None,
+            pending_scrolls: Vec::new(),
+        }
+    }
+    pub const fn size(&self) -> Size {
+        self.size
+    }
+    /// Starts over with a blank screen of the given size if the size changed,
+    /// as terminals tend to garble their contents while resizing.
+    pub fn resize(&mut self, size: Size) {
+        if size == self.size {
+            return;
+        }
+        self.size = size;
+        self.back = vec![vec![Cell::default(); size.width]; size.height];
+        self.front = None;
+        self.front_caret = None;
+        self.pending_scrolls.clear();
+    }
+    /// Scrolls the given rows on the terminal during the next `present`,
+    /// so that only the rows scrolled into view have to be sent afterwards.
+    /// Ignored if all of the rows would be scrolled out of view anyway.
+    pub fn scroll_rows(&mut self, rows: Range<RowIdx>, scroll: Scroll) {
+        let Some(front) = &mut self.front else {
+            return;
+        };
+        let Some(front_rows) = front.get_mut(rows.clone()) else {
+            return;
+        };
+        let (Scroll::Up(count) | Scroll::Down(count)) = scroll;
+        if count == 0 || count >= front_rows.len() {
+            return;
+        }
+        // the rows scrolled into view are blank, which the next frame has to draw over
+        let exposed = match scroll {
+            Scroll::Up(_) => {
+                front_rows.rotate_left(count);
+                front_rows.len().saturating_sub(count)..front_rows.len()
+            }
+            Scroll::Down(_) => {
+                front_rows.rotate_right(count);
+                0..count
+            }
+        };
+        for row in front_rows.get_mut(exposed).unwrap_or_default() {
+            *row = vec![Cell::default(); self.size.width];
+        }
+        self.pending_scrolls.push((rows, scroll));
+    }
+    /// Replaces the given row with the styled parts, filling the rest of it with blanks.
+    pub fn set_row<'a>(&mut self, row: RowIdx, parts: impl IntoIterator<Item = (&'a str, Style)>) {
+        let width = self.size.width;
+        let Some(cells) = self.back.get_mut(row) else {
+            return;
+        };
+        cells.clear();
+        'parts: for (string, style) in parts {
+            for grapheme in string.graphemes(true) {
+                let (grapheme, grapheme_width) = if grapheme.chars().any(char::is_control) {
+                    (" ", 1)
+                } else {
+                    (grapheme, grapheme.width().max(1))
+                };
+                if cells.len().saturating_add(grapheme_width) > width {
+                    break 'parts;
+                }
+                cells.push(Cell {
+                    grapheme: String::from(grapheme),
+                    style,
+                });
+                for _ in 1..grapheme_width {
+                    cells.push(Cell {
+                        grapheme: String::new(),
+                        style,
+                    });
+                }
+            }
+        }
+        cells.resize(width, Cell::default());
+    }
+    pub fn move_caret_to(&mut self, position: Position) {
+        self.caret = position;
+    }
+    pub fn set_caret_visible(&mut self, visible: bool) {
+        self.caret_visible = visible;
+    }
+    /// Returns the text of each row as of the last `present`.
+    #[cfg(test)]
+    pub fn presented_rows(&self) -> Vec<String> {
+        self.front
+            .iter()
+            .flatten()
+            .map(|row| row.iter().map(|cell| cell.grapheme.as_str()).collect())
+            .collect()
+    }
+    /// Returns the caret as of the last `present`, None if it is hidden.
+    #[cfg(test)]
+    pub fn presented_caret(&self) -> Option<Position> {
+        self.front_caret
+            .filter(|&(_, visible)| visible)
+            .map(|(position, _)| position)
+    }
+    /// Sends the cells which changed since the last call to `output`, followed by the caret.
+    pub fn present(&mut self, output: &mut dyn Write) -> Result<(), Error> {
+        let front = self.front.get_or_insert_with(|| {
+            // every row differs from an empty one, so all of them are drawn
+            vec![Vec::new(); self.size.height]
+        });
+        let caret = (self.caret, self.caret_visible);
+        let changed = front != &self.back || !self.pending_scrolls.is_empty();
+        if !changed && self.front_caret == Some(caret) {
+            return Ok(());
+        }
+        if changed {
+            output.queue(Hide)?;
+            // also resets the colors, so that the rows scrolled into view are blank
+            output.queue(SetAttribute(Reset))?;
+            for (rows, scroll) in self.pending_scrolls.drain(..) {
+                output.queue(SetScrollRegion(Some(rows)))?;
+                #[allow(clippy::as_conversions, clippy::cast_possible_truncation)]
+                match scroll {
+                    Scroll::Up(count) => output.queue(ScrollUp(count as u16))?,
+                    Scroll::Down(count) => output.queue(ScrollDown(count as u16))?,
+                };
+                output.queue(SetScrollRegion(None))?;
+            }
+            let mut style = Style::default();
+            for (row, (front_row, back_row)) in front.iter_mut().zip(&self.back).enumerate() {
+                if front_row != back_row {
+                    draw_row_changes(output, row, front_row, back_row, &mut style)?;
+                    front_row.clone_from(back_row);
+                }
+            }
+            apply_style(output, &mut style, Style::default())?;
+        }
+        #[allow(clippy::as_conversions, clippy::cast_possible_truncation)]
+        output.queue(MoveTo(self.caret.col as u16, self.caret.row as u16))?;
+        if self.caret_visible {
+            output.queue(Show)?;
+        } else if !changed {
+            output.queue(Hide)?;
+        }
+        self.front_caret = Some(caret);
+        Ok(())
+    }
+}
+
+/// Sends the spans of `back_row` which differ from `front_row`.
+/// `front_row` may be empty if the row is unknown, in which case the whole row is sent.
+fn draw_row_changes(
+    output: &mut dyn Write,
+    row: RowIdx,
+    front_row: &[Cell],
+    back_row: &[Cell],
+    style: &mut Style,
+) -> Result<(), Error> {
+    let width = back_row.len();
+    let differs = |col: ColIdx| front_row.get(col) != back_row.get(col);
+    // where the terminal's caret is after printing, if known
+    let mut caret: Option<ColIdx> = None;
+    let mut col = 0;
+    while col < width {
+        if !differs(col) {
+            col = col.saturating_add(1);
+            continue;
+        }
+        // a wide grapheme has to be printed as a whole
+        let mut start = col;
+        let is_continuation = |row: &[Cell], col| row.get(col).is_some_and(Cell::is_continuation);
+        while start > 0 && (is_continuation(back_row, start) || is_continuation(front_row, start)) {
+            start = start.saturating_sub(1);
+        }
+        let mut last_changed = col;
+        let mut end = col.saturating_add(1);
+        while end < width && end.saturating_sub(last_changed) <= MAX_REPRINTED_GAP {
+            if differs(end) {
+                last_changed = end;
+            }
+            end = end.saturating_add(1);
+        }
+        end = last_changed.saturating_add(1);
+        if caret != Some(start) {
+            #[allow(clippy::as_conversions, clippy::cast_possible_truncation)]
+            output.queue(MoveTo(start as u16, row as u16))?;
+        }
+        let rest = back_row.get(start..).unwrap_or_default();
+        if rest.iter().all(Cell::is_blank) {
+            apply_style(output, style, Style::default())?;
+            output.queue(Clear(ClearType::UntilNewLine))?;
+            return Ok(());
+        }
+        for cell in back_row.get(start..end).unwrap_or_default() {
+            if !cell.is_continuation() {
+                apply_style(output, style, cell.style)?;
+                output.queue(Print(&cell.grapheme))?;
+            }
+        }
+        caret = Some(end);
+        col = end;
+    }
+    Ok(())
+}
+
+fn apply_style(output: &mut dyn Write, current: &mut Style, style: Style) -> Result<(), Error> {
+    if current.reverse != style.reverse {
+        output.queue(SetAttribute(if style.reverse {
+            Reverse
+        } else {
+            NoReverse
+        }))?;
+    }
+    if current.foreground != style.foreground {
+        output.queue(SetForegroundColor(style.foreground.unwrap_or(Color::Reset)))?;
+    }
+    if current.background != style.background {
+        output.queue(SetBackgroundColor(style.background.unwrap_or(Color::Reset)))?;
+    }
+    *current = style;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn present(screen: &mut Screen) -> String {
+        let mut output = Vec::new();
+        screen.present(&mut output).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn test_present_sends_only_changes() {
+        let mut screen = Screen::new();
+        screen.resize(Size {
+            width: 10,
+            height: 2,
+        });
+        screen.set_row(0, [("hello", Style::default())]);
+        screen.set_row(1, [("world", Style::default())]);
+        let first = present(&mut screen);
+        assert!(first.contains("hello") && first.contains("world"));
+        assert_eq!(present(&mut screen), "");
+
+        screen.set_row(0, [("help", Style::default())]);
+        screen.set_row(1, [("world", Style::default())]);
+        let second = present(&mut screen);
+        // "p" replaces "l", and a blank the "o"
+        assert!(second.ends_with("\u{1b}[1;4Hp \u{1b}[1;1H"), "{second:?}");
+        assert!(!second.contains("world"));
+        screen.set_row(0, []);
+        assert!(present(&mut screen).contains("\u{1b}[1;1H\u{1b}[K"));
+
+        // a wide grapheme is printed as a whole when its right half changes
+        screen.set_row(1, [("日本", Style::default())]);
+        present(&mut screen);
+        screen.set_row(1, [("日x", Style::default())]);
+        let third = present(&mut screen);
+        assert!(third.contains("\u{1b}[2;3Hx"), "{third:?}");
+        screen.set_row(1, [("a日x", Style::default())]);
+        assert!(present(&mut screen).contains("a日x"));
+    }
+
+    #[test]
+    fn test_scroll_rows() {
+        let mut screen = Screen::new();
+        screen.resize(Size {
+            width: 4,
+            height: 4,
+        });
+        let set_rows = |screen: &mut Screen, rows: &[&str]| {
+            for (row, text) in rows.iter().enumerate() {
+                screen.set_row(row, [(*text, Style::default())]);
+            }
+        };
+        set_rows(&mut screen, &["a", "b", "c", "bar"]);
+        present(&mut screen);
+
+        screen.scroll_rows(0..3, Scroll::Up(1));
+        set_rows(&mut screen, &["b", "c", "d", "bar"]);
+        let output = present(&mut screen);
+        assert!(
+            output.contains("\u{1b}[1;3r\u{1b}[1S\u{1b}[r\u{1b}[3;1Hd"),
+            "{output:?}"
+        );
+        assert!(!output.contains('b') && !output.contains('c'));
+
+        screen.scroll_rows(0..3, Scroll::Down(2));
+        set_rows(&mut screen, &["x", "y", "b", "bar"]);
+        let output = present(&mut screen);
+        assert!(
+            output.contains("\u{1b}[1;3r\u{1b}[2T\u{1b}[r"),
+            "{output:?}"
+        );
+        assert!(output.contains('x') && output.contains('y') && !output.contains('b'));
+
+        // scrolling everything out of view is left to the diff
+        screen.scroll_rows(0..3, Scroll::Up(3));
+        assert!(screen.pending_scrolls.is_empty());
+    }
+}
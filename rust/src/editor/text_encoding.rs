@@ -0,0 +1,197 @@
+use chardetng::EncodingDetector;
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, UTF_8};
+use std::fmt;
+use std::io::{Error, ErrorKind};
+
+/// The encoding a file was read in, so that it can be written back the same way.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct TextEncoding {
+    encoding: &'static Encoding,
+    // whether the file starts with a byte order mark
+    bom: bool,
+}
+
+impl Default for TextEncoding {
+    fn default() -> Self {
+        Self {
+            encoding: UTF_8,
+            bom: false,
+        }
+    }
+}
+
+impl TextEncoding {
+    /// Detects the encoding of `bytes` and decodes them.
+    /// A byte order mark wins, then UTF-16 without a byte order mark (whose zero bytes
+    /// would be valid UTF-8), then UTF-8, and otherwise the most likely legacy encoding is guessed.
+    /// Also returns whether bytes invalid in the encoding were replaced,
+    /// in which case encoding the text again does not give back the same bytes.
+    pub fn decode(bytes: &[u8]) -> (String, Self, bool) {
+        if let Some((encoding, bom_len)) = Encoding::for_bom(bytes) {
+            let text = bytes.get(bom_len..).unwrap_or_default();
+            let (text, lossy) = encoding.decode_without_bom_handling(text);
+            return (
+                text.into_owned(),
+                Self {
+                    encoding,
+                    bom: true,
+                },
+                lossy,
+            );
+        }
+        let encoding = if let Some(encoding) = guess_utf16(bytes) {
+            encoding
+        } else if let Ok(text) = std::str::from_utf8(bytes) {
+            return (String::from(text), Self::default(), false);
+        } else {
+            let mut detector = EncodingDetector::new();
+            detector.feed(bytes, true);
+            detector.guess(None, true)
+        };
+        let (text, lossy) = encoding.decode_without_bom_handling(bytes);
+        (
+            text.into_owned(),
+            Self {
+                encoding,
+                bom: false,
+            },
+            lossy,
+        )
+    }
+    /// Encodes `text` for writing it back to the file.
+    /// Fails if the text contains characters the encoding cannot represent.
+    pub fn encode(&self, text: &str) -> Result<Vec<u8>, Error> {
+        let mut result = Vec::new();
+        if self.bom {
+            result.extend_from_slice(bom_of(self.encoding));
+        }
+        // encoding_rs only decodes UTF-16, so it is encoded by hand
+        if self.encoding == UTF_16LE {
+            result.extend(text.encode_utf16().flat_map(u16::to_le_bytes));
+        } else if self.encoding == UTF_16BE {
+            result.extend(text.encode_utf16().flat_map(u16::to_be_bytes));
+        } else {
+            let (bytes, _, had_errors) = self.encoding.encode(text);
+            if had_errors {
+                return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    format!("the text cannot be encoded as {self}"),
+                ));
+            }
+            result.extend_from_slice(&bytes);
+        }
+        Ok(result)
+    }
+}
+
+impl fmt::Display for TextEncoding {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        if self.bom && self.encoding == UTF_8 {
+            write!(formatter, "UTF-8 BOM")
+        } else {
+            write!(formatter, "{}", self.encoding.name())
+        }
+    }
+}
+
+/// Whether `bytes` look like binary data rather than text in any encoding:
+/// like git, files with a zero byte near the start count as binary, unless they are UTF-16.
+pub fn looks_binary(bytes: &[u8]) -> bool {
+    let start = bytes.get(..8000).unwrap_or(bytes);
+    start.contains(&0) && Encoding::for_bom(bytes).is_none() && guess_utf16(bytes).is_none()
+}
+
+fn bom_of(encoding: &'static Encoding) -> &'static [u8] {
+    if encoding == UTF_16LE {
+        b"\xFF\xFE"
+    } else if encoding == UTF_16BE {
+        b"\xFE\xFF"
+    } else {
+        b"\xEF\xBB\xBF"
+    }
+}
+
+/// Recognizes mostly-ASCII UTF-16 by the zero bytes in every other position.
+fn guess_utf16(bytes: &[u8]) -> Option<&'static Encoding> {
+    if bytes.len() < 2 || bytes.len().checked_rem(2) != Some(0) {
+        return None;
+    }
+    let (mut even_zeros, mut odd_zeros) = (0_usize, 0_usize);
+    for pair in bytes.chunks_exact(2) {
+        if pair.first() == Some(&0) {
+            even_zeros = even_zeros.saturating_add(1);
+        }
+        if pair.get(1) == Some(&0) {
+            odd_zeros = odd_zeros.saturating_add(1);
+        }
+    }
+    let pairs = bytes.len().checked_div(2).unwrap_or(0);
+    // at least half of the characters are ASCII, and the other half has no zero bytes
+    let mostly = |zeros: usize| zeros.saturating_mul(2) >= pairs;
+    if mostly(odd_zeros) && even_zeros == 0 {
+        Some(UTF_16LE)
+    } else if mostly(even_zeros) && odd_zeros == 0 {
+        Some(UTF_16BE)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(bytes: &[u8], expected_text: &str, expected_name: &str) {
+        let (text, encoding, lossy) = TextEncoding::decode(bytes);
+        assert!(!lossy);
+        assert_eq!(text, expected_text);
+        assert_eq!(encoding.to_string(), expected_name);
+        assert_eq!(encoding.encode(&text).unwrap(), bytes);
+    }
+
+    #[test]
+    fn test_decode_and_encode() {
+        round_trip(b"plain\n", "plain\n", "UTF-8");
+        round_trip(b"\xEF\xBB\xBFbom\n", "bom\n", "UTF-8 BOM");
+        round_trip(b"\xFF\xFEh\0i\0", "hi", "UTF-16LE");
+        round_trip(b"\0h\0i\0\n", "hi\n", "UTF-16BE");
+        round_trip(
+            "Grüße aus Köln, schöne Straße\n".as_bytes(),
+            "Grüße aus Köln, schöne Straße\n",
+            "UTF-8",
+        );
+        round_trip(
+            b"Gr\xFC\xDFe aus K\xF6ln, sch\xF6ne Stra\xDFe\n",
+            "Grüße aus Köln, schöne Straße\n",
+            "windows-1252",
+        );
+        round_trip(
+            b"\x82\xB1\x82\xF1\x82\xC9\x82\xBF\x82\xCD\x90\xA2\x8AE\n",
+            "こんにちは世界\n",
+            "Shift_JIS",
+        );
+    }
+
+    #[test]
+    fn test_looks_binary() {
+        assert!(!looks_binary(b"text\n"));
+        assert!(!looks_binary(b"\0h\0i"));
+        assert!(looks_binary(b"\x7fELF\x02\x01\x01\0\0\0"));
+    }
+
+    #[test]
+    fn test_encode_unrepresentable() {
+        let (_, encoding, _) = TextEncoding::decode(b"caf\xE9 cr\xE8me br\xFBl\xE9e");
+        assert_eq!(encoding.to_string(), "windows-1252");
+        assert!(encoding.encode("日本").is_err());
+    }
+
+    #[test]
+    fn test_decode_invalid() {
+        let bytes = b"\xEF\xBB\xBFcaf\xE9\n";
+        let (text, encoding, lossy) = TextEncoding::decode(bytes);
+        assert!(lossy);
+        assert_eq!(text, "caf\u{FFFD}\n");
+        assert_ne!(encoding.encode(&text).unwrap(), bytes);
+    }
+}
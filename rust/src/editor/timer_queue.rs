@@ -0,0 +1,109 @@
+use std::time::{Duration, Instant};
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Timer {
+    // the message in the message bar runs out
+    MessageExpiry,
+    // write the unsaved changes to the swap file
+    SwapFile,
+    // look for changes to the file by other programs
+    FileCheck,
+}
+
+struct ScheduledTimer {
+    timer: Timer,
+    deadline: Instant,
+    // repeating timers are rescheduled by this interval after they fired
+    interval: Option<Duration>,
+}
+
+/// Keeps track of the work which has to happen between keystrokes.
+/// Each kind of `Timer` is scheduled at most once at a time.
+#[derive(Default)]
+pub struct TimerQueue {
+    // sorted by deadline, the earliest first
+    timers: Vec<ScheduledTimer>,
+}
+
+impl TimerQueue {
+    /// Schedules a one-shot timer, replacing a pending timer of the same kind.
+    pub fn schedule_at(&mut self, timer: Timer, deadline: Instant) {
+        self.insert(ScheduledTimer {
+            timer,
+            deadline,
+            interval: None,
+        });
+    }
+    /// Schedules a timer firing every `interval`, replacing a pending timer of the same kind.
+    pub fn schedule_repeating(&mut self, timer: Timer, interval: Duration, now: Instant) {
+        self.insert(ScheduledTimer {
+            timer,
+            deadline: now.checked_add(interval).unwrap_or(now),
+            interval: Some(interval),
+        });
+    }
+    pub fn cancel(&mut self, timer: Timer) {
+        self.timers.retain(|scheduled| scheduled.timer != timer);
+    }
+    /// Returns how long to wait for input until the next timer is due,
+    /// or None if there is nothing scheduled.
+    pub fn time_until_next(&self, now: Instant) -> Option<Duration> {
+        self.timers
+            .first()
+            .map(|scheduled| scheduled.deadline.saturating_duration_since(now))
+    }
+    /// Removes and returns all timers which are due, rescheduling the repeating ones.
+    pub fn pop_expired(&mut self, now: Instant) -> Vec<Timer> {
+        let count = self
+            .timers
+            .iter()
+            .take_while(|scheduled| scheduled.deadline <= now)
+            .count();
+        let expired: Vec<ScheduledTimer> = self.timers.drain(..count).collect();
+        for scheduled in &expired {
+            if let Some(interval) = scheduled.interval {
+                self.schedule_repeating(scheduled.timer, interval, now);
+            }
+        }
+        expired.iter().map(|scheduled| scheduled.timer).collect()
+    }
+    fn insert(&mut self, scheduled: ScheduledTimer) {
+        self.cancel(scheduled.timer);
+        let idx = self
+            .timers
+            .partition_point(|other| other.deadline <= scheduled.deadline);
+        self.timers.insert(idx, scheduled);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_timer_queue() {
+        let now = Instant::now();
+        let second = Duration::from_secs(1);
+        let mut queue = TimerQueue::default();
+        assert_eq!(queue.time_until_next(now), None);
+
+        queue.schedule_at(Timer::MessageExpiry, now + second * 5);
+        queue.schedule_at(Timer::MessageExpiry, now + second * 3);
+        assert_eq!(queue.time_until_next(now), Some(second * 3));
+        assert!(queue.pop_expired(now + second).is_empty());
+        assert_eq!(
+            queue.pop_expired(now + second * 3),
+            vec![Timer::MessageExpiry]
+        );
+        assert_eq!(queue.time_until_next(now), None);
+
+        queue.schedule_repeating(Timer::MessageExpiry, second * 2, now);
+        assert_eq!(
+            queue.pop_expired(now + second * 2),
+            vec![Timer::MessageExpiry]
+        );
+        assert_eq!(queue.time_until_next(now + second * 2), Some(second * 2));
+        queue.cancel(Timer::MessageExpiry);
+        assert_eq!(queue.time_until_next(now), None);
+    }
+}
@@ -16,8 +16,14 @@ impl CommandBar {
     pub fn handle_edit_command(&mut self, command: Edit) {
         match command {
             Edit::Insert(c) => self.insert(c),
+            Edit::InsertTab => self.insert('\t'),
             Edit::DeleteBackward => self.delete_backward(),
-            Edit::Delete | Edit::InsertNewLine => {}
+            Edit::Delete
+            | Edit::InsertNewLine
+            | Edit::InsertLineBelow
+            | Edit::Dedent
+            | Edit::ToggleComment
+            | Edit::ToggleLineEnding => {}
         }
         self.set_needs_redraw(true);
     }
@@ -32,7 +38,7 @@ impl CommandBar {
         min(max_width, self.size.width)
     }
     pub fn value(&self) -> String {
-        self.value.to_string()
+        self.value.clone()
     }
     pub fn clear_value(&mut self) {
         self.value = String::default();
@@ -0,0 +1,255 @@
+use super::super::{
+    atomic_write::{write_atomically, Written},
+    command::Move,
+    is_writable, AnnotatedString, AnnotationType, DiskState, DocumentStatus, Settings, Terminal,
+};
+use super::UIComponent;
+use crate::prelude::{Position, RowIdx, Size};
+use std::cmp::min;
+use std::fmt::Write;
+use std::fs::read;
+use std::io::Error;
+use std::path::{Path, PathBuf};
+
+const BYTES_PER_ROW: usize = 16;
+// "00000000  " before the hex bytes
+const HEX_START_COL: usize = 10;
+// each byte takes two hex digits and a space
+const HEX_BYTE_WIDTH: usize = 3;
+
+/// Shows a binary file as a hex dump, with offset, hex bytes and ASCII columns,
+/// and allows overwriting single bytes by typing hex digits.
+#[derive(Default)]
+pub struct HexView {
+    bytes: Vec<u8>,
+    path: PathBuf,
+    // the byte the caret is on
+    cursor: usize,
+    // whether the next typed digit replaces the low nibble of the byte under the caret
+    at_low_nibble: bool,
+    // the first visible row
+    scroll_offset: usize,
+    pub dirty: usize,
+    read_only: bool,
+    // the file as last read or written, to notice changes by other programs
+    disk_state: Option<DiskState>,
+    settings: Settings,
+    needs_redraw: bool,
+    size: Size,
+}
+
+impl HexView {
+    pub fn load(filename: &str, settings: Settings) -> Result<Self, Error> {
+        let bytes = read(filename)?;
+        let path = PathBuf::from(filename);
+        Ok(Self {
+            disk_state: DiskState::of_contents(&path, &bytes).ok(),
+            read_only: settings.read_only || !is_writable(&path),
+            bytes,
+            path,
+            settings,
+            ..Self::default()
+        })
+    }
+    /// Reads the file again, discarding all unsaved changes.
+    /// The caret stays at the same offset, as far as possible.
+    pub fn reload(&mut self) -> Result<(), Error> {
+        let bytes = read(&self.path)?;
+        self.disk_state = DiskState::of_contents(&self.path, &bytes).ok();
+        self.bytes = bytes;
+        self.dirty = 0;
+        self.at_low_nibble = false;
+        self.move_cursor_to(self.cursor);
+        Ok(())
+    }
+    /// Returns the current state of the file on disk
+    /// if another program changed it since it was last read or written.
+    pub fn changed_disk_state(&mut self) -> Option<DiskState> {
+        self.disk_state.as_mut()?.changed_state(&self.path)
+    }
+    pub const fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+    pub fn save(&mut self) -> Result<Written, Error> {
+        let written = write_atomically(&self.path, &self.bytes, self.settings.backup)?;
+        self.disk_state = DiskState::of_contents(&self.path, &self.bytes).ok();
+        self.dirty = 0;
+        Ok(written)
+    }
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+    pub fn document_status(&self) -> DocumentStatus {
+        DocumentStatus {
+            filename: self
+                .path
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string()),
+            total_lines: self.row_count(),
+            current_line_idx: self.cursor_row().saturating_add(1),
+            modified: self.dirty > 0,
+            read_only: self.read_only,
+            binary: true,
+            ..DocumentStatus::default()
+        }
+    }
+    /// Replaces one nibble of the byte under the caret with the hex digit `c`,
+    /// moving on to the next byte after the low nibble.
+    /// Returns false if `c` is not a hex digit.
+    pub fn overwrite_nibble(&mut self, c: char) -> bool {
+        let Some(digit) = c.to_digit(16).and_then(|digit| u8::try_from(digit).ok()) else {
+            return false;
+        };
+        let Some(byte) = self.bytes.get_mut(self.cursor) else {
+            return true;
+        };
+        if self.at_low_nibble {
+            *byte = (*byte & 0xF0) | digit;
+            self.at_low_nibble = false;
+            self.move_cursor_to(self.cursor.saturating_add(1));
+        } else {
+            *byte = (*byte & 0x0F) | (digit << 4);
+            self.at_low_nibble = true;
+        }
+        self.dirty = self.dirty.saturating_add(1);
+        self.set_needs_redraw(true);
+        true
+    }
+    pub fn handle_move_command(&mut self, command: Move) {
+        let page = self.size.height.saturating_sub(1).max(1);
+        let page_bytes = page.saturating_mul(BYTES_PER_ROW);
+        let row_start = self.cursor_row().saturating_mul(BYTES_PER_ROW);
+        let cursor = match command {
+            Move::Left if self.at_low_nibble => self.cursor,
+            Move::Left => self.cursor.saturating_sub(1),
+            Move::Right => self.cursor.saturating_add(1),
+            Move::Up => self.cursor.saturating_sub(BYTES_PER_ROW),
+            Move::Down => self.cursor.saturating_add(BYTES_PER_ROW),
+            Move::PageUp => self.cursor.saturating_sub(page_bytes),
+            Move::PageDown => self.cursor.saturating_add(page_bytes),
+            Move::StartOfLine => row_start,
+            Move::EndOfLine => row_start.saturating_add(BYTES_PER_ROW.saturating_sub(1)),
+        };
+        self.at_low_nibble = false;
+        self.move_cursor_to(cursor);
+    }
+    pub fn caret_position(&self) -> Position {
+        let col_in_row = self.cursor.checked_rem(BYTES_PER_ROW).unwrap_or(0);
+        Position {
+            col: HEX_START_COL
+                .saturating_add(col_in_row.saturating_mul(HEX_BYTE_WIDTH))
+                .saturating_add(usize::from(self.at_low_nibble)),
+            row: self.cursor_row().saturating_sub(self.scroll_offset),
+        }
+    }
+    fn move_cursor_to(&mut self, cursor: usize) {
+        self.cursor = min(cursor, self.bytes.len().saturating_sub(1));
+        self.scroll_into_view();
+        self.set_needs_redraw(true);
+    }
+    fn scroll_into_view(&mut self) {
+        let row = self.cursor_row();
+        if row < self.scroll_offset {
+            self.scroll_offset = row;
+        } else if row >= self.scroll_offset.saturating_add(self.size.height) {
+            self.scroll_offset = row
+                .saturating_add(1)
+                .saturating_sub(self.size.height.max(1));
+        }
+    }
+    fn cursor_row(&self) -> usize {
+        self.cursor.checked_div(BYTES_PER_ROW).unwrap_or(0)
+    }
+    fn row_count(&self) -> usize {
+        self.bytes.len().div_ceil(BYTES_PER_ROW)
+    }
+    fn format_row(&self, row: usize) -> Option<AnnotatedString> {
+        let start = row.saturating_mul(BYTES_PER_ROW);
+        let end = min(start.saturating_add(BYTES_PER_ROW), self.bytes.len());
+        let bytes = self
+            .bytes
+            .get(start..end)
+            .filter(|bytes| !bytes.is_empty())?;
+        let hex = bytes.iter().fold(String::new(), |mut hex, byte| {
+            let _ = write!(hex, "{byte:02x} ");
+            hex
+        });
+        let ascii: String = bytes
+            .iter()
+            .map(|&byte| {
+                if byte.is_ascii_graphic() || byte == b' ' {
+                    char::from(byte)
+                } else {
+                    '.'
+                }
+            })
+            .collect();
+        let hex_width = BYTES_PER_ROW.saturating_mul(HEX_BYTE_WIDTH);
+        let text = format!("{start:08x}  {hex:<hex_width$}|{ascii}|");
+        let mut result = AnnotatedString::from(&text);
+        if (start..end).contains(&self.cursor) {
+            // all characters are ASCII, so byte indices are column indices
+            let ascii_col = HEX_START_COL
+                .saturating_add(hex_width)
+                .saturating_add(1)
+                .saturating_add(self.cursor.saturating_sub(start));
+            result.push(
+                AnnotationType::SelectedMatch,
+                ascii_col,
+                ascii_col.saturating_add(1),
+            );
+        }
+        Some(result)
+    }
+}
+
+impl UIComponent for HexView {
+    fn set_needs_redraw(&mut self, value: bool) {
+        self.needs_redraw = value;
+    }
+    fn needs_redraw(&self) -> bool {
+        self.needs_redraw
+    }
+    fn set_size(&mut self, to: Size) {
+        self.size = to;
+        self.scroll_into_view();
+    }
+    fn draw(&mut self, origin_row: RowIdx) -> Result<(), Error> {
+        for row in 0..self.size.height {
+            let current_row = origin_row.saturating_add(row);
+            match self.format_row(self.scroll_offset.saturating_add(row)) {
+                Some(line) => Terminal::print_annotated_row(current_row, &line)?,
+                None => Terminal::print_row(current_row, "~")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_overwrite_and_format() {
+        let mut hex_view = HexView {
+            bytes: b"AB\0\x7f".to_vec(),
+            ..HexView::default()
+        };
+        assert!(!hex_view.overwrite_nibble('x'));
+        assert!(hex_view.overwrite_nibble('6'));
+        assert_eq!(hex_view.caret_position().col, HEX_START_COL + 1);
+        assert!(hex_view.overwrite_nibble('1'));
+        assert_eq!(hex_view.bytes, b"aB\0\x7f");
+        assert_eq!(hex_view.cursor, 1);
+        assert_eq!(hex_view.dirty, 2);
+
+        hex_view.handle_move_command(Move::EndOfLine);
+        assert_eq!(hex_view.cursor, 3);
+        assert_eq!(
+            hex_view.format_row(0).unwrap().to_string(),
+            format!("00000000  61 42 00 7f {:36}|aB..|", "")
+        );
+        assert!(hex_view.format_row(1).is_none());
+    }
+}
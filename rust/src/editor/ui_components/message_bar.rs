@@ -1,38 +1,99 @@
 use super::super::terminal::Terminal;
+use super::super::{AnnotatedString, AnnotationType};
 use super::UIComponent;
 use crate::prelude::{RowIdx, Size};
 use std::{
+    collections::VecDeque,
     io::Error,
-    time::{Duration, Instant},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
 const DEFAULT_DURATION: Duration = Duration::new(5, 0);
+const MAX_HISTORY_LEN: usize = 200;
 
+#[derive(Default, Eq, PartialEq, Debug, Copy, Clone)]
+pub enum Severity {
+    #[default]
+    Info,
+    Warning,
+    Error,
+}
+impl Severity {
+    fn annotation_type(self) -> Option<AnnotationType> {
+        match self {
+            Self::Info => None,
+            Self::Warning => Some(AnnotationType::Warning),
+            Self::Error => Some(AnnotationType::Error),
+        }
+    }
+    fn label(self) -> &'static str {
+        match self {
+            Self::Info => "info",
+            Self::Warning => "warning",
+            Self::Error => "error",
+        }
+    }
+}
+
+#[derive(Clone)]
 struct Message {
     text: String,
+    severity: Severity,
     time: Instant,
+    // wall clock time, only used for display
+    timestamp: SystemTime,
     duration: Duration,
 }
 impl Default for Message {
     fn default() -> Self {
-        Self::new(String::new())
+        Self::new(String::new(), Severity::default())
     }
 }
 impl Message {
-    fn new(text: String) -> Self {
+    fn new(text: String, severity: Severity) -> Self {
         Self {
             text,
+            severity,
             time: Instant::now(),
+            timestamp: SystemTime::now(),
             duration: DEFAULT_DURATION,
         }
     }
+    fn expires_at(&self) -> Instant {
+        self.time.checked_add(self.duration).unwrap_or(self.time)
+    }
     fn is_expired(&self) -> bool {
-        Instant::now().duration_since(self.time) > self.duration
+        Instant::now() >= self.expires_at()
+    }
+    fn to_annotated_string(&self, text: &str) -> AnnotatedString {
+        let mut result = AnnotatedString::from(text);
+        if let Some(annotation_type) = self.severity.annotation_type() {
+            result.push(annotation_type, 0, text.len());
+        }
+        result
+    }
+    /// Formats the timestamp as `HH:MM:SS` in UTC.
+    fn time_string(&self) -> String {
+        let seconds = self
+            .timestamp
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |duration| duration.as_secs());
+        let hours = seconds.checked_div(3600).unwrap_or(0).checked_rem(24);
+        let minutes = seconds.checked_div(60).unwrap_or(0).checked_rem(60);
+        let seconds = seconds.checked_rem(60);
+        format!(
+            "{:02}:{:02}:{:02}",
+            hours.unwrap_or(0),
+            minutes.unwrap_or(0),
+            seconds.unwrap_or(0)
+        )
     }
 }
 
 #[derive(Default)]
 pub struct MessageBar {
     message: Message,
+    // all non-empty messages, the oldest first
+    history: VecDeque<Message>,
     needs_redraw: bool,
     width: usize,
     cleared_after_expiry: bool,
@@ -40,10 +101,45 @@ pub struct MessageBar {
 
 impl MessageBar {
     pub fn update_message(&mut self, new_message: &str) {
-        self.message = Message::new(new_message.to_string());
+        self.push_message(new_message, Severity::Info);
+    }
+    pub fn update_warning(&mut self, new_message: &str) {
+        self.push_message(new_message, Severity::Warning);
+    }
+    pub fn update_error(&mut self, new_message: &str) {
+        self.push_message(new_message, Severity::Error);
+    }
+    fn push_message(&mut self, new_message: &str, severity: Severity) {
+        self.message = Message::new(new_message.to_string(), severity);
+        if !new_message.is_empty() {
+            if self.history.len() >= MAX_HISTORY_LEN {
+                self.history.pop_front();
+            }
+            self.history.push_back(self.message.clone());
+        }
         self.cleared_after_expiry = false;
         self.set_needs_redraw(true);
     }
+    /// Returns when the current message has to be cleared,
+    /// or None if it already has been.
+    pub fn expires_at(&self) -> Option<Instant> {
+        (!self.cleared_after_expiry).then(|| self.message.expires_at())
+    }
+    /// Returns all messages so far, the oldest first, prefixed with time and severity.
+    pub fn history(&self) -> Vec<AnnotatedString> {
+        self.history
+            .iter()
+            .map(|message| {
+                let text = format!(
+                    "{} [{}] {}",
+                    message.time_string(),
+                    message.severity.label(),
+                    message.text
+                );
+                message.to_annotated_string(&text)
+            })
+            .collect()
+    }
 }
 
 impl UIComponent for MessageBar {
@@ -61,12 +157,38 @@ impl UIComponent for MessageBar {
             // write blank string to clear the message bar
             //
             self.cleared_after_expiry = true;
-            ""
+            AnnotatedString::default()
         } else {
-            &self.message.text
+            self.message.to_annotated_string(&self.message.text)
         };
-        let result = Terminal::print_row(origin_row, line_text);
+        let result = Terminal::print_annotated_row(origin_row, &line_text);
         debug_assert!(result.is_ok(), "Failed to render status_bar");
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_history() {
+        let mut message_bar = MessageBar::default();
+        message_bar.update_message("saved");
+        message_bar.update_message("");
+        message_bar.update_error("failed");
+        let history: Vec<String> = message_bar
+            .history()
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(history.len(), 2);
+        assert!(history[0].ends_with(" [info] saved"));
+        assert!(history[1].ends_with(" [error] failed"));
+
+        for _ in 0..MAX_HISTORY_LEN {
+            message_bar.update_warning("again");
+        }
+        assert_eq!(message_bar.history().len(), MAX_HISTORY_LEN);
+    }
+}
@@ -8,3 +8,7 @@ pub use message_bar::MessageBar;
 mod message_bar;
 pub use command_bar::CommandBar;
 mod command_bar;
+pub use pager::Pager;
+mod pager;
+pub use hex_view::HexView;
+mod hex_view;
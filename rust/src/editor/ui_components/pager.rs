@@ -0,0 +1,78 @@
+use super::super::{command::Move, AnnotatedString, Terminal};
+use super::UIComponent;
+use crate::prelude::{LineIdx, RowIdx, Size};
+use std::cmp::min;
+use std::io::Error;
+
+/// A read-only, scrollable list of lines with a title row,
+/// shown in place of the view, like the message history.
+#[derive(Default)]
+pub struct Pager {
+    title: String,
+    lines: Vec<AnnotatedString>,
+    scroll_offset: LineIdx,
+    needs_redraw: bool,
+    size: Size,
+}
+
+impl Pager {
+    pub fn new(title: &str, lines: Vec<AnnotatedString>) -> Self {
+        Self {
+            title: title.to_string(),
+            lines,
+            ..Self::default()
+        }
+    }
+    /// Scrolls to the end, so that the latest lines are visible.
+    pub fn scroll_to_end(&mut self) {
+        self.scroll_offset = self.max_scroll_offset();
+        self.set_needs_redraw(true);
+    }
+    pub fn handle_move_command(&mut self, command: Move) {
+        let page = self.text_height().saturating_sub(1).max(1);
+        self.scroll_offset = match command {
+            Move::Up | Move::Left => self.scroll_offset.saturating_sub(1),
+            Move::Down | Move::Right => self.scroll_offset.saturating_add(1),
+            Move::PageUp => self.scroll_offset.saturating_sub(page),
+            Move::PageDown => self.scroll_offset.saturating_add(page),
+            Move::StartOfLine => 0,
+            Move::EndOfLine => self.max_scroll_offset(),
+        };
+        self.scroll_offset = min(self.scroll_offset, self.max_scroll_offset());
+        self.set_needs_redraw(true);
+    }
+    // the first row is taken by the title
+    fn text_height(&self) -> usize {
+        self.size.height.saturating_sub(1)
+    }
+    fn max_scroll_offset(&self) -> LineIdx {
+        self.lines.len().saturating_sub(self.text_height())
+    }
+}
+
+impl UIComponent for Pager {
+    fn set_needs_redraw(&mut self, value: bool) {
+        self.needs_redraw = value;
+    }
+    fn needs_redraw(&self) -> bool {
+        self.needs_redraw
+    }
+    fn set_size(&mut self, to: Size) {
+        self.size = to;
+        self.scroll_offset = min(self.scroll_offset, self.max_scroll_offset());
+    }
+    fn draw(&mut self, origin_row: RowIdx) -> Result<(), Error> {
+        let title = format!("{} (Esc to close)", self.title);
+        Terminal::print_invert_row(origin_row, &title)?;
+        for row in 1..self.size.height {
+            let current_row = origin_row.saturating_add(row);
+            let line_idx = self.scroll_offset.saturating_add(row).saturating_sub(1);
+            if let Some(line) = self.lines.get(line_idx) {
+                Terminal::print_annotated_row(current_row, line)?;
+            } else {
+                Terminal::print_row(current_row, "")?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -1,14 +1,17 @@
-use crate::prelude::{RowIdx, Size};
+use crate::prelude::{ColIdx, RowIdx, Size};
 
 use super::super::{DocumentStatus, Terminal};
 use super::{UIComponent, View};
 use std::io::Error;
+use std::ops::Range;
 
 #[derive(Default)]
 pub struct StatusBar {
     pub document_status: DocumentStatus,
     pub needs_redraw: bool,
     pub width: usize,
+    // the columns showing the line ending, to toggle it when clicked
+    line_ending_cols: Range<ColIdx>,
 }
 
 impl StatusBar {
@@ -19,7 +22,18 @@ impl StatusBar {
             total_lines: view.buffer.height(),
             current_line_idx: view.text_location.line_idx.saturating_add(1),
             modified: view.buffer.dirty > 0,
+            read_only: view.buffer.is_read_only(),
+            encoding: view.buffer.file_info.get_encoding(),
+            line_ending: view.buffer.line_ending,
+            final_newline: view.buffer.final_newline,
+            binary: false,
         };
+        self.set_document_status(new_status);
+    }
+    pub fn is_line_ending_at(&self, col: ColIdx) -> bool {
+        self.line_ending_cols.contains(&col)
+    }
+    pub fn set_document_status(&mut self, new_status: DocumentStatus) {
         if self.document_status != new_status {
             self.document_status = new_status;
             self.set_needs_redraw(true);
@@ -40,18 +54,41 @@ impl UIComponent for StatusBar {
     fn draw(&mut self, origin_row: RowIdx) -> Result<(), Error> {
         let filename_string = self.document_status.filename_string();
         let modified_string = self.document_status.modified_string();
+        let read_only_string = self.document_status.read_only_string();
         let total_lines_string = self.document_status.total_lines_string();
         let position_string = self.document_status.position_string();
-        let file_type_string = self.document_status.file_type.as_ref().map_or_else(
-            String::default,
-            |file_type| format!("{file_type:?} | " ),
-        );
+        let line_ending_string = self.document_status.line_ending_string();
+        let file_type_string = self
+            .document_status
+            .file_type
+            .as_ref()
+            .map_or_else(String::default, |file_type| format!("{file_type:?} | "));
 
-        let left = format!("{filename_string}{modified_string} - {total_lines_string}");
-        let right = format!("{file_type_string}{position_string}");
+        let left =
+            format!("{filename_string}{modified_string}{read_only_string} - {total_lines_string}");
+        let encoding_string = self.document_status.encoding;
+        let right = if self.document_status.binary {
+            format!("Hex | {position_string}")
+        } else {
+            format!(
+                "{file_type_string}{encoding_string} | {line_ending_string} | {position_string}"
+            )
+        };
         // minus 1 for the space between left and right
         let reminder_len = self.width.saturating_sub(left.len()).saturating_sub(1);
         let mut line_text = format!("{left} {right:>reminder_len$}");
+        self.line_ending_cols = if self.document_status.binary {
+            0..0
+        } else {
+            // the line ending is followed by " | " and the position at the end of the row
+            let end = line_text
+                .len()
+                .saturating_sub(position_string.len())
+                .saturating_sub(3);
+            let start = end.saturating_sub(line_ending_string.len());
+            let col = |byte_idx| line_text.get(..byte_idx).map_or(0, |s| s.chars().count());
+            col(start)..col(end)
+        };
         line_text.truncate(self.width);
         let result = Terminal::print_invert_row(origin_row, &line_text);
         debug_assert!(result.is_ok(), "Failed to render status_bar");
@@ -1,34 +1,221 @@
 use super::{GraphemeIdx, Highlighter, Line};
 use crate::editor::annotated_string::AnnotatedString;
-use crate::prelude::{LineIdx, Location};
-use std::fs::{read_to_string, File};
-use std::io::{Error, Write};
+use crate::prelude::{ByteIdx, ColIdx, LineIdx, Location};
+use ropey::Rope;
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::cmp::{max, min};
+use std::collections::HashMap;
+use std::fs::read;
+use std::io::{Error, ErrorKind};
 use std::ops::Range;
+use std::path::Path;
+use std::rc::Rc;
 
+use crate::editor::atomic_write::{write_atomically, Written};
 use crate::editor::file_info::FileInfo;
+use crate::editor::{is_writable, looks_binary, DiskState, LineEnding, Settings, TextEncoding};
+
+// Lines are only split into fragments when they are needed, e.g. for drawing.
+// Those are cached, and the cache is dropped when it grows beyond this many lines.
+const MAX_CACHED_LINES: usize = 1024;
+// Line changes which nobody took are collapsed into a reset beyond this many.
+const MAX_LINE_CHANGES: usize = 1024;
+// The partner of a bracket is searched for this many lines beyond the visible ones,
+// so that an unmatched bracket does not scan the whole file on every draw.
+const MAX_BRACKET_SEARCH_LINES: usize = 1000;
+
+/// A change of the lines of the buffer, so that caches kept outside of it can follow.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum LineChange {
+    // the line at the index changed
+    Edited(LineIdx),
+    // a line was inserted at the index
+    Inserted(LineIdx),
+    // the line at the index was removed
+    Removed(LineIdx),
+    // all lines were replaced
+    Reset,
+}
 
-#[derive(Default)]
 pub struct Buffer {
-    pub lines: Vec<Line>,
+    // the text, with every line terminated by "\n" regardless of the line ending on disk
+    rope: Rope,
+    // the lines built from the rope so far, by line index
+    line_cache: RefCell<HashMap<LineIdx, Rc<Line>>>,
+    // the changes since they were last taken
+    line_changes: Vec<LineChange>,
     pub file_info: FileInfo,
     pub dirty: usize,
+    pub line_ending: LineEnding,
+    // whether the last line is terminated by a line ending
+    pub final_newline: bool,
+    // whether the file could be opened for writing when it was loaded
+    writable: bool,
+    // whether bytes invalid in the encoding were replaced when loading,
+    // so that saving would not write back what was read
+    lossy: bool,
+    settings: Settings,
+}
+
+impl Default for Buffer {
+    fn default() -> Self {
+        Self {
+            rope: Rope::new(),
+            line_cache: RefCell::default(),
+            line_changes: Vec::new(),
+            file_info: FileInfo::default(),
+            dirty: 0,
+            line_ending: LineEnding::default(),
+            final_newline: true,
+            writable: true,
+            lossy: false,
+            settings: Settings::default(),
+        }
+    }
 }
 
 impl Buffer {
+    pub fn set_settings(&mut self, settings: Settings) {
+        self.settings = settings;
+        for line in self.line_cache.get_mut().values_mut() {
+            Rc::make_mut(line).set_tab_width(settings.tab_width);
+        }
+    }
     pub fn height(&self) -> LineIdx {
-        self.lines.len()
+        // the rope counts the empty remainder after the last "\n" as a line
+        self.rope.len_lines().saturating_sub(1)
     }
     pub fn is_empty(&self) -> bool {
-        self.lines.is_empty()
+        self.height() == 0
+    }
+    /// Returns the line at the given index, splitting it into fragments if it isn't cached yet.
+    pub fn line(&self, line_idx: LineIdx) -> Option<Rc<Line>> {
+        if line_idx >= self.height() {
+            return None;
+        }
+        if let Some(line) = self.line_cache.borrow().get(&line_idx) {
+            return Some(Rc::clone(line));
+        }
+        let line = Rc::new(self.build_line(line_idx));
+        let mut line_cache = self.line_cache.borrow_mut();
+        if line_cache.len() >= MAX_CACHED_LINES {
+            line_cache.clear();
+        }
+        line_cache.insert(line_idx, Rc::clone(&line));
+        Some(line)
+    }
+    /// Returns the changes of the lines since the last call.
+    pub fn take_line_changes(&mut self) -> Vec<LineChange> {
+        std::mem::take(&mut self.line_changes)
+    }
+    fn record_change(&mut self, change: LineChange) {
+        // a reset makes the earlier changes irrelevant
+        if change == LineChange::Reset || self.line_changes.len() >= MAX_LINE_CHANGES {
+            self.line_changes = vec![LineChange::Reset];
+        }
+        if change != LineChange::Reset {
+            self.line_changes.push(change);
+        }
+    }
+    /// Returns the text of the given line without its "\n", without building a `Line`.
+    fn line_text(&self, line_idx: LineIdx) -> Cow<'_, str> {
+        let line = self.rope.line(line_idx);
+        line.slice(..line.len_chars().saturating_sub(1)).into()
+    }
+    fn build_line(&self, line_idx: LineIdx) -> Line {
+        Line::with_tab_width(&self.line_text(line_idx), self.settings.tab_width)
+    }
+    /// Removes the given line from the cache to change it, without copying it if possible.
+    fn take_line(&mut self, line_idx: LineIdx) -> Line {
+        self.line_cache.get_mut().remove(&line_idx).map_or_else(
+            || self.build_line(line_idx),
+            |line| Rc::try_unwrap(line).unwrap_or_else(|line| (*line).clone()),
+        )
+    }
+    /// Changes a single line with `edit`, which must not add or remove line breaks
+    /// and returns the byte range of the line it replaced. Only that range is written back
+    /// to the rope, so that edits don't get slower with the length of the line.
+    /// Returns false if there is no such line.
+    fn edit_line<F>(&mut self, line_idx: LineIdx, edit: F) -> bool
+    where
+        F: FnOnce(&mut Line) -> Range<ByteIdx>,
+    {
+        if line_idx >= self.height() {
+            return false;
+        }
+        let mut line = self.take_line(line_idx);
+        let old_len = line.len();
+        let replaced = edit(&mut line);
+        debug_assert!(!line.contains('\n'));
+        let replacement_end = replaced
+            .end
+            .saturating_add(line.len())
+            .saturating_sub(old_len);
+        let line_start = self.rope.line_to_byte(line_idx);
+        let start = self
+            .rope
+            .byte_to_char(line_start.saturating_add(replaced.start));
+        let end = self
+            .rope
+            .byte_to_char(line_start.saturating_add(replaced.end));
+        self.rope.remove(start..end);
+        self.rope
+            .insert(start, &line[replaced.start..replacement_end]);
+        self.line_cache.get_mut().insert(line_idx, Rc::new(line));
+        self.record_change(LineChange::Edited(line_idx));
+        self.dirty = self.dirty.saturating_add(1);
+        true
+    }
+    /// Moves the cached lines after `line_idx` down by one line if `inserted`, else up by one,
+    /// after a line break has been inserted or removed at the end of `line_idx`.
+    fn shift_cached_lines(&mut self, line_idx: LineIdx, inserted: bool) {
+        let line_cache = self.line_cache.get_mut();
+        *line_cache = line_cache
+            .drain()
+            .filter_map(|(idx, line)| {
+                if idx <= line_idx {
+                    Some((idx, line))
+                } else if inserted {
+                    Some((idx.saturating_add(1), line))
+                } else if idx == line_idx.saturating_add(1) {
+                    // the line which got joined
+                    None
+                } else {
+                    Some((idx.saturating_sub(1), line))
+                }
+            })
+            .collect();
+    }
+    /// Returns the character at the given location,
+    /// if the grapheme there consists of a single character.
+    pub fn char_at(&self, at: Location) -> Option<char> {
+        let line = self.line(at.line_idx)?;
+        let grapheme = line.get_grapheme(at.grapheme_idx)?;
+        let mut chars = grapheme.chars();
+        let c = chars.next();
+        chars.next().is_none().then_some(c).flatten()
     }
     pub fn grapheme_count(&self, line_idx: LineIdx) -> GraphemeIdx {
-        self.lines.get(line_idx).map_or(0, Line::grapheme_count)
+        self.line(line_idx).map_or(0, |line| line.grapheme_count())
     }
     pub fn width_until(&self, line_idx: LineIdx, until: GraphemeIdx) -> GraphemeIdx {
-        self.lines
-            .get(line_idx)
+        self.line(line_idx)
             .map_or(0, |line| line.width_until(until))
     }
+    /// Returns the index of the grapheme covering the given column of the line,
+    /// or the end of the line if the column lies beyond it.
+    pub fn grapheme_idx_at(&self, line_idx: LineIdx, col: ColIdx) -> GraphemeIdx {
+        self.line(line_idx)
+            .map_or(0, |line| line.grapheme_idx_at(col))
+    }
+    /// Returns the byte index of the given location in the rope, clamped to its line.
+    fn location_to_byte(&self, at: Location) -> ByteIdx {
+        let byte_idx = self.line(at.line_idx).map_or(0, |line| {
+            line.grapheme_idx_to_byte_idx(min(at.grapheme_idx, line.grapheme_count()))
+        });
+        self.rope.line_to_byte(at.line_idx).saturating_add(byte_idx)
+    }
 
     pub fn get_highlighted_substring(
         &self,
@@ -36,26 +223,166 @@ impl Buffer {
         range: Range<GraphemeIdx>,
         highlighter: &Highlighter,
     ) -> Option<AnnotatedString> {
-        self.lines.get(line_idx).map(|line| {
+        self.line(line_idx).map(|line| {
             line.get_annotated_visible_substr(range, Some(&highlighter.get_annotations(line_idx)))
         })
     }
     pub fn highlight(&self, line_idx: LineIdx, highlighter: &mut Highlighter) {
-        if let Some(line) = self.lines.get(line_idx) {
-            highlighter.highlight(line_idx, line);
+        if highlighter.is_up_to_date(line_idx) {
+            highlighter.skip(line_idx);
+        } else if let Some(line) = self.line(line_idx) {
+            highlighter.highlight(line_idx, &line);
         }
     }
+    /// Marks the matches of the search query in the given lines,
+    /// which need not be highlighted otherwise.
+    pub fn highlight_search_results(&self, lines: Range<LineIdx>, highlighter: &mut Highlighter) {
+        for line_idx in lines {
+            if let Some(line) = self.line(line_idx) {
+                highlighter.highlight_search_results(line_idx, &line);
+            }
+        }
+    }
+    fn highlight_until(&self, line_idx: LineIdx, highlighter: &mut Highlighter) {
+        for current_line_idx in highlighter.highlighted_line_count()..=line_idx {
+            self.highlight(current_line_idx, highlighter);
+        }
+    }
+    /// Highlights the bracket at the given location together with its partner,
+    /// if that is no further than `MAX_BRACKET_SEARCH_LINES` away from the `visible` lines.
+    /// Brackets inside of strings, chars and comments are ignored.
+    pub fn highlight_matching_bracket(
+        &self,
+        at: Location,
+        visible: Range<LineIdx>,
+        highlighter: &mut Highlighter,
+    ) {
+        let Some(bracket) = self.char_at(at) else {
+            return;
+        };
+        let (partner, forward) = match bracket {
+            '(' => (')', true),
+            '[' => (']', true),
+            '{' => ('}', true),
+            ')' => ('(', false),
+            ']' => ('[', false),
+            '}' => ('{', false),
+            _ => return,
+        };
+        let Some(line) = self.line(at.line_idx) else {
+            return;
+        };
+        let start_byte_idx = line.grapheme_idx_to_byte_idx(at.grapheme_idx);
+        self.highlight_until(at.line_idx, highlighter);
+        if !highlighter.is_code(at.line_idx, start_byte_idx) {
+            return;
+        }
+
+        // the bracket at the caret itself opens the first level
+        let mut depth: usize = 0;
+        let mut is_match = |highlighter: &Highlighter, line_idx, byte_idx, c| {
+            if !highlighter.is_code(line_idx, byte_idx) {
+                return false;
+            }
+            if c == bracket {
+                depth = depth.saturating_add(1);
+            } else if c == partner {
+                depth = depth.saturating_sub(1);
+                return depth == 0;
+            }
+            false
+        };
+        let found = if forward {
+            let end = min(
+                visible.end.saturating_add(MAX_BRACKET_SEARCH_LINES),
+                self.height(),
+            );
+            (at.line_idx..end).find_map(|line_idx| {
+                self.highlight_until(line_idx, highlighter);
+                let line = self.line(line_idx)?;
+                let from = if line_idx == at.line_idx {
+                    start_byte_idx
+                } else {
+                    0
+                };
+                line[from..]
+                    .char_indices()
+                    .map(|(idx, c)| (idx.saturating_add(from), c))
+                    .find(|&(byte_idx, c)| is_match(highlighter, line_idx, byte_idx, c))
+                    .map(|(byte_idx, _)| (line_idx, byte_idx))
+            })
+        } else {
+            let start = visible.start.saturating_sub(MAX_BRACKET_SEARCH_LINES);
+            (start..=at.line_idx).rev().find_map(|line_idx| {
+                let line = self.line(line_idx)?;
+                let until = if line_idx == at.line_idx {
+                    start_byte_idx.saturating_add(1)
+                } else {
+                    line.len()
+                };
+                line[..until]
+                    .char_indices()
+                    .rev()
+                    .find(|&(byte_idx, c)| is_match(highlighter, line_idx, byte_idx, c))
+                    .map(|(byte_idx, _)| (line_idx, byte_idx))
+            })
+        };
+        if let Some(partner_position) = found {
+            highlighter
+                .highlight_matching_brackets([(at.line_idx, start_byte_idx), partner_position]);
+        }
+    }
+    /// Marks the part of the given lines which lies within `selection`.
+    pub fn highlight_selection(
+        &self,
+        selection: &Range<Location>,
+        lines: Range<LineIdx>,
+        highlighter: &mut Highlighter,
+    ) {
+        let first = max(lines.start, selection.start.line_idx);
+        let end = min(lines.end, selection.end.line_idx.saturating_add(1));
+        let ranges = (first..end)
+            .filter_map(|line_idx| {
+                let line = self.line(line_idx)?;
+                let byte_idx = |grapheme_idx| {
+                    line.grapheme_idx_to_byte_idx(min(grapheme_idx, line.grapheme_count()))
+                };
+                let start = if line_idx == selection.start.line_idx {
+                    byte_idx(selection.start.grapheme_idx)
+                } else {
+                    0
+                };
+                let end = if line_idx == selection.end.line_idx {
+                    byte_idx(selection.end.grapheme_idx)
+                } else {
+                    line.len()
+                };
+                Some((line_idx, start..end))
+            })
+            .collect();
+        highlighter.highlight_selection(ranges);
+    }
     pub fn insert_newline(&mut self, at: Location) -> bool {
         let Location {
             grapheme_idx,
             line_idx,
         } = at;
         if line_idx >= self.height() {
-            self.lines.push(Line::default());
+            self.record_change(LineChange::Inserted(self.height()));
+            self.rope.insert_char(self.rope.len_chars(), '\n');
         } else {
             // we have a valid line_idx
-            let second_half = self.lines[line_idx].split_off(grapheme_idx);
-            self.lines.insert(line_idx.saturating_add(1), second_half);
+            let mut line = self.take_line(line_idx);
+            let second_half = line.split_off(grapheme_idx);
+            let byte_idx = self.rope.line_to_byte(line_idx).saturating_add(line.len());
+            self.rope
+                .insert_char(self.rope.byte_to_char(byte_idx), '\n');
+            self.shift_cached_lines(line_idx, true);
+            let line_cache = self.line_cache.get_mut();
+            line_cache.insert(line_idx, Rc::new(line));
+            line_cache.insert(line_idx.saturating_add(1), Rc::new(second_half));
+            self.record_change(LineChange::Edited(line_idx));
+            self.record_change(LineChange::Inserted(line_idx.saturating_add(1)));
         }
         self.dirty = self.dirty.saturating_add(1);
         true
@@ -71,15 +398,67 @@ impl Buffer {
         }
 
         // below here, we have a valid line_idx
-        if grapheme_idx < self.lines[line_idx].len() {
-            self.lines[line_idx].remove(grapheme_idx, 1);
-        } else if line_idx < self.height().saturating_sub(1) {
-            let next_line = self.lines.remove(line_idx.saturating_add(1));
-            self.lines[line_idx].append(&next_line);
-        } else {
+        if grapheme_idx < self.grapheme_count(line_idx) {
+            return self.edit_line(line_idx, |line| line.remove(grapheme_idx, 1));
+        }
+        if line_idx.saturating_add(1) >= self.height() {
             // the last line, the last character
             return false;
         }
+        // join the next line by removing the line break in between
+        let mut line = self.take_line(line_idx);
+        let next_line = self.take_line(line_idx.saturating_add(1));
+        line.append(&next_line);
+        let line_break = self
+            .rope
+            .line_to_char(line_idx.saturating_add(1))
+            .saturating_sub(1);
+        self.rope.remove(line_break..line_break.saturating_add(1));
+        self.shift_cached_lines(line_idx, false);
+        self.line_cache.get_mut().insert(line_idx, Rc::new(line));
+        self.record_change(LineChange::Removed(line_idx.saturating_add(1)));
+        self.record_change(LineChange::Edited(line_idx));
+        self.dirty = self.dirty.saturating_add(1);
+        true
+    }
+    /// Removes the text between the two locations, joining the lines at both ends.
+    /// The line break at the end of the last line stays. Returns false if nothing changed.
+    pub fn remove_range(&mut self, range: Range<Location>) -> bool {
+        let Some(last_line_idx) = self.height().checked_sub(1) else {
+            return false;
+        };
+        let end = min(
+            range.end,
+            Location {
+                line_idx: last_line_idx,
+                grapheme_idx: self.grapheme_count(last_line_idx),
+            },
+        );
+        let start = range.start;
+        if start >= end {
+            return false;
+        }
+        let start_char = self.rope.byte_to_char(self.location_to_byte(start));
+        let end_char = self.rope.byte_to_char(self.location_to_byte(end));
+        self.rope.remove(start_char..end_char);
+        let removed_lines = end.line_idx.saturating_sub(start.line_idx);
+        let line_cache = self.line_cache.get_mut();
+        *line_cache = line_cache
+            .drain()
+            .filter_map(|(idx, line)| {
+                if idx < start.line_idx {
+                    Some((idx, line))
+                } else if idx > end.line_idx {
+                    Some((idx.saturating_sub(removed_lines), line))
+                } else {
+                    None
+                }
+            })
+            .collect();
+        for _ in 0..removed_lines {
+            self.record_change(LineChange::Removed(start.line_idx.saturating_add(1)));
+        }
+        self.record_change(LineChange::Edited(start.line_idx));
         self.dirty = self.dirty.saturating_add(1);
         true
     }
@@ -94,55 +473,147 @@ impl Buffer {
             return false;
         }
 
-        let string = c.to_string();
-
         // append a new line
         if line_idx == self.height() {
-            self.lines.push(Line::from(&string));
+            self.record_change(LineChange::Inserted(line_idx));
+            self.rope.insert(self.rope.len_chars(), &format!("{c}\n"));
             self.dirty = self.dirty.saturating_add(1);
             return true;
         }
 
         // insert a new character in an existing line
-        if let Some(line) = self.lines.get_mut(line_idx) {
-            line.insert(grapheme_idx, &string);
-            self.dirty = self.dirty.saturating_add(1);
-            return true;
+        self.edit_line(line_idx, |line| line.insert(grapheme_idx, &c.to_string()))
+    }
+    /// Removes one level of indentation from the given line:
+    /// either a leading tab, or the spaces back to the previous tab stop.
+    /// Returns the number of removed graphemes.
+    pub fn dedent(&mut self, line_idx: LineIdx) -> GraphemeIdx {
+        let tab_width = self.settings.tab_width;
+        if line_idx >= self.height() {
+            return 0;
         }
-
-        // maybe dead code, but the compiler doesn't know that
-        false
+        let line = self.line_text(line_idx);
+        let count = if line.starts_with('\t') {
+            1
+        } else {
+            let spaces = line.chars().take_while(|&c| c == ' ').count();
+            match spaces.checked_rem(tab_width) {
+                Some(0) | None => min(spaces, tab_width),
+                Some(offset) => offset,
+            }
+        };
+        if count > 0 {
+            self.edit_line(line_idx, |line| line.remove(0, count));
+        }
+        count
+    }
+    /// Comments out the given lines with the line comment `token`,
+    /// aligned to the smallest indentation among them,
+    /// or uncomments them if all of them are already commented out.
+    /// Blank lines are left untouched. Returns false if nothing changed.
+    pub fn toggle_comment(&mut self, range: Range<LineIdx>, token: &str) -> bool {
+        let end = min(range.end, self.height());
+        let indent_of = |line: &str| line.chars().take_while(|c| c.is_whitespace()).count();
+        let lines: Vec<(LineIdx, Cow<str>)> = (range.start..end)
+            .map(|line_idx| (line_idx, self.line_text(line_idx)))
+            .filter(|(_, line)| !line.trim().is_empty())
+            .collect();
+        if lines.is_empty() {
+            return false;
+        }
+        let is_commented = lines
+            .iter()
+            .all(|(_, line)| line.trim_start().starts_with(token));
+        let edits: Vec<(LineIdx, GraphemeIdx, Option<GraphemeIdx>)> = if is_commented {
+            lines
+                .iter()
+                .map(|(line_idx, line)| {
+                    let mut length = token.chars().count();
+                    if line.trim_start()[token.len()..].starts_with(' ') {
+                        length = length.saturating_add(1);
+                    }
+                    (*line_idx, indent_of(line), Some(length))
+                })
+                .collect()
+        } else {
+            let indent = lines
+                .iter()
+                .map(|(_, line)| indent_of(line))
+                .min()
+                .unwrap_or(0);
+            lines
+                .iter()
+                .map(|(line_idx, _)| (*line_idx, indent, None))
+                .collect()
+        };
+        let comment = format!("{token} ");
+        for (line_idx, at, remove_length) in edits {
+            self.edit_line(line_idx, |line| match remove_length {
+                Some(length) => line.remove(at, length),
+                None => line.insert(at, &comment),
+            });
+        }
+        true
+    }
+    /// Whether edits have to be rejected, because the file is not writable, could not
+    /// be decoded without losing bytes, or the editor was started with `--readonly`.
+    pub const fn is_read_only(&self) -> bool {
+        !self.writable || self.lossy || self.settings.read_only
+    }
+    pub const fn is_lossy(&self) -> bool {
+        self.lossy
     }
     pub fn load(filename: &str) -> Result<Self, Error> {
-        let contents = read_to_string(filename)?;
-        let mut lines = Vec::new();
-        for line in contents.lines() {
-            lines.push(Line::from(line));
+        let bytes = read(filename)?;
+        if looks_binary(&bytes) {
+            return Err(Error::new(ErrorKind::InvalidData, "binary file"));
         }
-        Ok(Self {
-            lines,
+        let (contents, encoding, lossy) = TextEncoding::decode(&bytes);
+        let mut buffer = Self {
             file_info: FileInfo::from(filename),
-            dirty: 0,
-        })
+            writable: is_writable(Path::new(filename)),
+            lossy,
+            ..Self::default()
+        };
+        buffer.set_contents(&contents);
+        buffer.file_info.set_encoding(encoding);
+        let disk_state = DiskState::of_contents(Path::new(filename), &bytes).ok();
+        buffer.file_info.set_disk_state(disk_state);
+        Ok(buffer)
+    }
+    /// Replaces all lines with the given contents, e.g. when recovering a swap file.
+    /// The line ending style and the final newline are taken from the contents.
+    pub fn set_contents(&mut self, contents: &str) {
+        // like `str::lines`, treat both "\n" and "\r\n" as line breaks
+        let mut text = if contents.contains("\r\n") {
+            Cow::Owned(contents.replace("\r\n", "\n"))
+        } else {
+            Cow::Borrowed(contents)
+        };
+        if !text.is_empty() && !text.ends_with('\n') {
+            text.to_mut().push('\n');
+        }
+        self.rope = Rope::from_str(&text);
+        self.line_cache.get_mut().clear();
+        self.record_change(LineChange::Reset);
+        self.line_ending = LineEnding::detect(contents);
+        // an empty file gets a final newline as soon as it has content
+        self.final_newline = contents.is_empty() || contents.ends_with('\n');
     }
     pub fn search_forward(&self, query: &str, from: Location) -> Option<Location> {
-        // search from the current line to the end
-        for (line_idx, line) in self.lines.iter().enumerate().skip(from.line_idx) {
+        // search from the current line to the end, then wrap around to the beginning
+        let line_indices = (from.line_idx..self.height()).chain(0..from.line_idx);
+        for line_idx in line_indices {
+            // only build the lines which may contain a match
+            if !self.line_text(line_idx).contains(query) {
+                continue;
+            }
             let from_grapheme_idx = if line_idx == from.line_idx {
                 from.grapheme_idx
             } else {
                 0
             };
-            if let Some(grapheme_idx) = line.search_forward(query, from_grapheme_idx) {
-                return Some(Location {
-                    grapheme_idx,
-                    line_idx,
-                });
-            }
-        }
-        // wrap around to the beginning
-        for (line_idx, line) in self.lines.iter().enumerate().take(from.line_idx) {
-            let from_grapheme_idx = 0;
+            let line = self.line(line_idx)?;
             if let Some(grapheme_idx) = line.search_forward(query, from_grapheme_idx) {
                 return Some(Location {
                     grapheme_idx,
@@ -156,23 +627,20 @@ impl Buffer {
         if query.is_empty() {
             return None;
         }
-        let mut is_first = true;
-        for (line_idx, line) in self
-            .lines
-            .iter()
-            .enumerate()
-            .rev()
-            .cycle()
-            .skip(
-                self.lines
-                    .len()
-                    .saturating_sub(from.line_idx)
-                    .saturating_sub(1),
-            )
-            .take(self.lines.len().saturating_add(1))
-        {
-            let from_grapheme_idx = if is_first {
-                is_first = false;
+        let height = self.height();
+        let start = min(from.line_idx, height.saturating_sub(1));
+        // search from the current line to the beginning, wrap around to the end,
+        // and end with the rest of the current line
+        for step in 0..=height {
+            let line_idx = start
+                .saturating_add(height)
+                .saturating_sub(step)
+                .checked_rem(height)?;
+            if !self.line_text(line_idx).contains(query) {
+                continue;
+            }
+            let line = self.line(line_idx)?;
+            let from_grapheme_idx = if step == 0 {
                 from.grapheme_idx
             } else {
                 line.grapheme_count()
@@ -186,24 +654,383 @@ impl Buffer {
         }
         None
     }
-    pub fn save(&mut self) -> Result<(), Error> {
+    /// Returns the whole contents as they are written to disk.
+    pub fn contents(&self) -> String {
+        let mut result = String::from(&self.rope);
+        if !self.final_newline {
+            result.pop();
+        }
+        let line_ending = self.line_ending.as_str();
+        if line_ending == "\n" {
+            result
+        } else {
+            result.replace('\n', line_ending)
+        }
+    }
+    pub fn toggle_line_ending(&mut self) {
+        self.line_ending = self.line_ending.toggle();
+        self.dirty = self.dirty.saturating_add(1);
+    }
+    pub fn save(&mut self) -> Result<Written, Error> {
         self.save_to_file()
     }
-    pub fn save_as(&mut self, filename: &str) -> Result<(), Error> {
-        let file_info = FileInfo::from(filename);
+    pub fn save_as(&mut self, filename: &str) -> Result<Written, Error> {
+        let mut file_info = FileInfo::from(filename);
+        // write the new file in the encoding the text was read in
+        file_info.set_encoding(self.file_info.get_encoding());
+        if let Some(path) = file_info.get_path() {
+            self.writable = is_writable(path);
+            // the new file holds the text as it is now
+            self.lossy = false;
+        }
         self.file_info = file_info;
         self.save_to_file()
     }
-    pub fn save_to_file(&mut self) -> Result<(), Error> {
-        if let Some(path) = &self.file_info.get_path() {
-            let mut file = File::create(path)?;
-            for line in &self.lines {
-                writeln!(file, "{line}")?;
-            }
+    pub fn save_to_file(&mut self) -> Result<Written, Error> {
+        if let Some(path) = self.file_info.get_path() {
+            let bytes = self.file_info.get_encoding().encode(&self.contents())?;
+            let written = write_atomically(path, &bytes, self.settings.backup)?;
+            let disk_state = DiskState::of_contents(path, &bytes).ok();
+            self.file_info.set_disk_state(disk_state);
             self.dirty = 0;
-            Ok(())
+            Ok(written)
         } else {
-            Err(Error::new(std::io::ErrorKind::Other, "No file path"))
+            Err(Error::other("No file path"))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::SyntaxCache;
+    use super::*;
+    use crate::editor::{Annotation, AnnotationType, FileType};
+
+    fn matching_brackets(
+        lines: &[&str],
+        at: Location,
+        visible: Range<LineIdx>,
+    ) -> Vec<(LineIdx, usize)> {
+        let mut buffer = Buffer::default();
+        buffer.set_contents(&lines.join("\n"));
+        let mut syntax_cache = SyntaxCache::default();
+        syntax_cache.update(FileType::Rust, &buffer.take_line_changes());
+        let mut highlighter = Highlighter::new(None, None, &mut syntax_cache);
+        buffer.highlight_matching_bracket(at, visible, &mut highlighter);
+        (0..buffer.height())
+            .flat_map(|line_idx| {
+                highlighter
+                    .get_annotations(line_idx)
+                    .into_iter()
+                    .filter(|annotation| {
+                        matches!(annotation.annotation_type, AnnotationType::MatchingBracket)
+                    })
+                    .map(move |annotation| (line_idx, annotation.start_byte_idx))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_save_preserves_line_endings() {
+        let path = std::env::temp_dir().join(format!("hecto-test-{}.txt", std::process::id()));
+        let filename = path.to_str().unwrap();
+        for contents in ["a\r\nb\r\n", "a\nb", "a\r\n\r\nb", ""] {
+            std::fs::write(&path, contents).unwrap();
+            let mut buffer = Buffer::load(filename).unwrap();
+            buffer.save().unwrap();
+            assert_eq!(std::fs::read_to_string(&path).unwrap(), contents);
+        }
+        let mut buffer = Buffer::load(filename).unwrap();
+        buffer.insert_char('x', Location::default());
+        buffer.toggle_line_ending();
+        buffer.save().unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "x\r\n");
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_save_as_keeps_encoding() {
+        let dir = std::env::temp_dir();
+        let path = dir.join(format!("hecto-test-{}-latin1.txt", std::process::id()));
+        let copy = dir.join(format!("hecto-test-{}-copy.txt", std::process::id()));
+        let bytes = b"Gr\xFC\xDFe aus K\xF6ln, sch\xF6ne Stra\xDFe\n";
+        std::fs::write(&path, bytes).unwrap();
+        let mut buffer = Buffer::load(path.to_str().unwrap()).unwrap();
+        buffer.save_as(copy.to_str().unwrap()).unwrap();
+        assert_eq!(std::fs::read(&copy).unwrap(), bytes);
+        std::fs::remove_file(&path).unwrap();
+        std::fs::remove_file(&copy).unwrap();
+    }
+
+    #[test]
+    fn test_lossy_file_is_read_only() {
+        let path =
+            std::env::temp_dir().join(format!("hecto-test-{}-lossy.txt", std::process::id()));
+        std::fs::write(&path, b"\xEF\xBB\xBFcaf\xE9\n").unwrap();
+        let buffer = Buffer::load(path.to_str().unwrap()).unwrap();
+        assert!(buffer.is_lossy());
+        assert!(buffer.is_read_only());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_save_as_makes_writable() {
+        let path = std::env::temp_dir().join(format!("hecto-test-{}-copy.rs", std::process::id()));
+        let mut buffer = Buffer {
+            writable: false,
+            ..Buffer::default()
+        };
+        buffer.set_contents("x\n");
+        assert!(buffer.is_read_only());
+        buffer.save_as(path.to_str().unwrap()).unwrap();
+        assert!(!buffer.is_read_only());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_edits_keep_cached_lines_in_sync() {
+        let mut buffer = Buffer::default();
+        buffer.set_contents("ab\ncd\nef");
+        let at = |line_idx, grapheme_idx| Location {
+            grapheme_idx,
+            line_idx,
+        };
+        let cached_lines = |buffer: &Buffer| -> Vec<String> {
+            (0..buffer.height())
+                .map(|line_idx| buffer.line(line_idx).unwrap().to_string())
+                .collect()
+        };
+        assert_eq!(cached_lines(&buffer), vec!["ab", "cd", "ef"]);
+
+        assert!(buffer.insert_newline(at(0, 1)));
+        assert_eq!(cached_lines(&buffer), vec!["a", "b", "cd", "ef"]);
+        assert!(buffer.insert_char('x', at(2, 2)));
+        assert!(buffer.remove_char(at(1, 1)));
+        assert_eq!(cached_lines(&buffer), vec!["a", "bcdx", "ef"]);
+        assert!(buffer.insert_char('y', at(3, 0)));
+        assert!(buffer.insert_newline(at(4, 0)));
+        assert!(!buffer.remove_char(at(4, 0)));
+        assert_eq!(cached_lines(&buffer), vec!["a", "bcdx", "ef", "y", ""]);
+        // the empty last line has no line ending, like "ef" had none when loaded
+        assert_eq!(buffer.contents(), "a\nbcdx\nef\ny\n");
+
+        // the rope has to agree with the cache
+        buffer.line_cache.get_mut().clear();
+        assert_eq!(cached_lines(&buffer), vec!["a", "bcdx", "ef", "y", ""]);
+    }
+
+    #[test]
+    fn test_remove_range() {
+        let mut buffer = Buffer::default();
+        buffer.set_contents("ab\ncd\nef\ngh");
+        let at = |line_idx, grapheme_idx| Location {
+            grapheme_idx,
+            line_idx,
+        };
+        // cache the lines around the change
+        for line_idx in 0..4 {
+            buffer.line(line_idx);
+        }
+        assert!(buffer.remove_range(at(0, 1)..at(2, 1)));
+        assert_eq!(buffer.contents(), "af\ngh");
+        assert_eq!(buffer.line(1).unwrap().to_string(), "gh");
+        assert_eq!(
+            buffer.take_line_changes(),
+            [
+                LineChange::Reset,
+                LineChange::Removed(1),
+                LineChange::Removed(1),
+                LineChange::Edited(0)
+            ]
+        );
+        assert!(!buffer.remove_range(at(1, 1)..at(1, 1)));
+        // beyond the end, the final line break stays
+        assert!(buffer.remove_range(at(1, 1)..at(2, 0)));
+        assert_eq!(buffer.contents(), "af\ng");
+        buffer.line_cache.get_mut().clear();
+        assert_eq!(buffer.line(1).unwrap().to_string(), "g");
+    }
+
+    #[test]
+    fn test_highlight_selection() {
+        let mut buffer = Buffer::default();
+        buffer.set_contents("緑ab\ncd\nef\ngh");
+        let at = |line_idx, grapheme_idx| Location {
+            grapheme_idx,
+            line_idx,
+        };
+        let mut syntax_cache = SyntaxCache::default();
+        let mut highlighter = Highlighter::new(None, None, &mut syntax_cache);
+        // only the visible lines 0 to 2 are marked
+        buffer.highlight_selection(&(at(0, 1)..at(3, 1)), 0..3, &mut highlighter);
+        let selected: Vec<(usize, usize)> = (0..4)
+            .flat_map(|line_idx| highlighter.get_annotations(line_idx))
+            .filter(|annotation| annotation.annotation_type == AnnotationType::Selection)
+            .map(|annotation| (annotation.start_byte_idx, annotation.end_byte_idx))
+            .collect();
+        assert_eq!(selected, [(3, 5), (0, 2), (0, 2)]);
+    }
+
+    #[test]
+    fn test_toggle_comment() {
+        let mut buffer = Buffer::default();
+        buffer.set_contents("    let a = 1;\n\n  // b\n\tc");
+        let strings = |buffer: &Buffer| -> Vec<String> {
+            (0..buffer.height())
+                .map(|line_idx| buffer.line_text(line_idx).to_string())
+                .collect()
+        };
+        assert!(buffer.toggle_comment(0..3, "//"));
+        assert_eq!(
+            strings(&buffer),
+            vec!["  //   let a = 1;", "", "  // // b", "\tc"]
+        );
+        assert!(buffer.toggle_comment(0..3, "//"));
+        assert_eq!(
+            strings(&buffer),
+            vec!["    let a = 1;", "", "  // b", "\tc"]
+        );
+        assert!(buffer.toggle_comment(3..4, "//"));
+        assert_eq!(strings(&buffer)[3], "\t// c");
+        assert!(buffer.toggle_comment(2..3, "//"));
+        assert_eq!(strings(&buffer)[2], "  b");
+        assert!(!buffer.toggle_comment(1..2, "//"));
+    }
+
+    #[test]
+    fn test_highlight_search_results() {
+        let mut buffer = Buffer::default();
+        buffer.set_contents("let x = 1;\nlet y = x;\nlet z = x;\n");
+        let mut syntax_cache = SyntaxCache::default();
+        syntax_cache.update(FileType::Rust, &buffer.take_line_changes());
+        let mut highlighter = Highlighter::new(None, None, &mut syntax_cache);
+        for line_idx in 0..buffer.height() {
+            buffer.highlight(line_idx, &mut highlighter);
+        }
+
+        // a search query does not require highlighting the lines again
+        let mut highlighter = Highlighter::new(Some("x"), None, &mut syntax_cache);
+        assert!((0..buffer.height()).all(|line_idx| highlighter.is_up_to_date(line_idx)));
+        // only the given lines are searched
+        buffer.highlight_search_results(1..2, &mut highlighter);
+        let matches = |line_idx| {
+            highlighter
+                .get_annotations(line_idx)
+                .into_iter()
+                .filter(|annotation| matches!(annotation.annotation_type, AnnotationType::Match))
+                .map(|annotation| annotation.start_byte_idx)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(matches(0), []);
+        assert_eq!(matches(1), [8]);
+        assert_eq!(matches(2), []);
+    }
+
+    #[test]
+    fn test_incremental_highlighting() {
+        // returns the annotations of all lines, and how many lines were highlighted again
+        fn highlight(
+            buffer: &mut Buffer,
+            syntax_cache: &mut SyntaxCache,
+        ) -> (Vec<Vec<Annotation>>, usize) {
+            syntax_cache.update(FileType::Rust, &buffer.take_line_changes());
+            let mut highlighter = Highlighter::new(None, None, syntax_cache);
+            let mut highlighted_count = 0;
+            for line_idx in 0..buffer.height() {
+                if !highlighter.is_up_to_date(line_idx) {
+                    highlighted_count += 1;
+                }
+                buffer.highlight(line_idx, &mut highlighter);
+            }
+            let annotations = (0..buffer.height())
+                .map(|line_idx| highlighter.get_annotations(line_idx))
+                .collect();
+            (annotations, highlighted_count)
         }
+        let at = |line_idx, grapheme_idx| Location {
+            grapheme_idx,
+            line_idx,
+        };
+        let mut buffer = Buffer::default();
+        buffer.set_contents("fn a() {}\nlet s = \"x\";\n// c\nlet n = 1;\nfn b() {}");
+        let mut syntax_cache = SyntaxCache::default();
+        assert_eq!(highlight(&mut buffer, &mut syntax_cache).1, 5);
+        assert_eq!(highlight(&mut buffer, &mut syntax_cache).1, 0);
+
+        let mut check = |buffer: &mut Buffer, expected_count| {
+            let (annotations, count) = highlight(buffer, &mut syntax_cache);
+            let mut fresh_buffer = Buffer::default();
+            fresh_buffer.set_contents(&buffer.contents());
+            let (expected, _) = highlight(&mut fresh_buffer, &mut SyntaxCache::default());
+            assert_eq!(annotations, expected);
+            assert_eq!(count, expected_count);
+        };
+        // the state after the edited line stays the same
+        assert!(buffer.insert_char('x', at(0, 3)));
+        check(&mut buffer, 1);
+        // opening a comment changes the state of all following lines
+        assert!(buffer.insert_char('*', at(1, 0)));
+        assert!(buffer.insert_char('/', at(1, 0)));
+        check(&mut buffer, 4);
+        // the lines after the split stay in the comment
+        assert!(buffer.insert_newline(at(2, 2)));
+        check(&mut buffer, 2);
+        assert!(buffer.remove_char(at(1, 0)));
+        check(&mut buffer, 5);
+        // joining lines re-highlights only the joined line
+        assert!(buffer.remove_char(at(1, 0)));
+        assert!(buffer.remove_char(at(2, 2)));
+        check(&mut buffer, 2);
+        assert!(buffer.insert_char('y', at(buffer.height(), 0)));
+        check(&mut buffer, 1);
+    }
+
+    #[test]
+    fn test_highlight_matching_bracket() {
+        let lines = ["fn main() {", "    let s = \"}\"; // }", "}"];
+        let at = |line_idx, grapheme_idx| Location {
+            grapheme_idx,
+            line_idx,
+        };
+        let all = 0..lines.len();
+        assert_eq!(
+            matching_brackets(&lines, at(0, 10), all.clone()),
+            vec![(0, 10), (2, 0)]
+        );
+        assert_eq!(
+            matching_brackets(&lines, at(2, 0), all.clone()),
+            vec![(0, 10), (2, 0)]
+        );
+        assert_eq!(
+            matching_brackets(&lines, at(0, 7), all.clone()),
+            vec![(0, 7), (0, 8)]
+        );
+        // brackets in strings are ignored
+        assert_eq!(matching_brackets(&lines, at(1, 13), all.clone()), vec![]);
+        assert_eq!(matching_brackets(&lines, at(0, 0), all), vec![]);
+    }
+
+    #[test]
+    fn test_matching_bracket_search_is_limited() {
+        let mut lines = vec!["{"];
+        lines.extend(["x"; MAX_BRACKET_SEARCH_LINES - 1]);
+        lines.push("}");
+        let start = Location::default();
+        let end = Location {
+            grapheme_idx: 0,
+            line_idx: lines.len().saturating_sub(1),
+        };
+        // the partner is just within reach of the visible lines
+        assert_eq!(
+            matching_brackets(&lines, start, 0..1),
+            vec![(0, 0), (end.line_idx, 0)]
+        );
+        assert_eq!(
+            matching_brackets(&lines, end, end.line_idx..lines.len()),
+            vec![(0, 0), (end.line_idx, 0)]
+        );
+        // but not beyond
+        lines.insert(1, "x");
+        assert_eq!(matching_brackets(&lines, start, 0..1), vec![]);
     }
 }
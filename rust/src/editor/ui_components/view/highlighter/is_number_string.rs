@@ -74,73 +74,73 @@ mod tests {
     fn test_is_number_string() {
         // Valid numbers:
 
-        assert_eq!(is_number_string("1"), true);
-        assert_eq!(is_number_string("2"), true);
-        assert_eq!(is_number_string("3"), true);
-        assert_eq!(is_number_string("4"), true);
-        assert_eq!(is_number_string("5"), true);
-        assert_eq!(is_number_string("6"), true);
-        assert_eq!(is_number_string("7"), true);
-        assert_eq!(is_number_string("8"), true);
-        assert_eq!(is_number_string("9"), true);
-        assert_eq!(is_number_string("0"), true);
-        assert_eq!(is_number_string("100"), true);
-        assert_eq!(is_number_string("1234567"), true);
-        assert_eq!(is_number_string("1.0"), true);
-        assert_eq!(is_number_string("2.0"), true);
-        assert_eq!(is_number_string("3.0"), true);
-        assert_eq!(is_number_string("0.0"), true);
-        assert_eq!(is_number_string("1e10"), true);
-        assert_eq!(is_number_string("20e50"), true);
-        assert_eq!(is_number_string("10.3e5"), true);
-        assert_eq!(is_number_string("1_00"), true);
-        assert_eq!(is_number_string("1_000_1"), true);
-        assert_eq!(is_number_string("1_000_000_000"), true);
-        assert_eq!(is_number_string("0x1"), true);
-        assert_eq!(is_number_string("0X2"), true);
-        assert_eq!(is_number_string("0b1"), true);
-        assert_eq!(is_number_string("0B0"), true);
-        assert_eq!(is_number_string("0X10F"), true);
-        assert_eq!(is_number_string("0o1"), true);
+        assert!(is_number_string("1"));
+        assert!(is_number_string("2"));
+        assert!(is_number_string("3"));
+        assert!(is_number_string("4"));
+        assert!(is_number_string("5"));
+        assert!(is_number_string("6"));
+        assert!(is_number_string("7"));
+        assert!(is_number_string("8"));
+        assert!(is_number_string("9"));
+        assert!(is_number_string("0"));
+        assert!(is_number_string("100"));
+        assert!(is_number_string("1234567"));
+        assert!(is_number_string("1.0"));
+        assert!(is_number_string("2.0"));
+        assert!(is_number_string("3.0"));
+        assert!(is_number_string("0.0"));
+        assert!(is_number_string("1e10"));
+        assert!(is_number_string("20e50"));
+        assert!(is_number_string("10.3e5"));
+        assert!(is_number_string("1_00"));
+        assert!(is_number_string("1_000_1"));
+        assert!(is_number_string("1_000_000_000"));
+        assert!(is_number_string("0x1"));
+        assert!(is_number_string("0X2"));
+        assert!(is_number_string("0b1"));
+        assert!(is_number_string("0B0"));
+        assert!(is_number_string("0X10F"));
+        assert!(is_number_string("0o1"));
 
         // Invalid numbers:
 
-        assert_eq!(is_number_string("1a"), false);
-        assert_eq!(is_number_string("2b"), false);
-        assert_eq!(is_number_string("3c"), false);
-        assert_eq!(is_number_string("4d"), false);
-        assert_eq!(is_number_string("5e"), false);
-        assert_eq!(is_number_string("6f"), false);
-        assert_eq!(is_number_string("7g"), false);
-        assert_eq!(is_number_string("8h"), false);
-        assert_eq!(is_number_string("9i"), false);
-        assert_eq!(is_number_string("0j"), false);
-        assert_eq!(is_number_string("100a200b300c400d500"), false);
-        assert_eq!(is_number_string("u32"), false);
-        assert_eq!(is_number_string("i8"), false);
-        assert_eq!(is_number_string("f64"), false);
-        assert_eq!(is_number_string("1.1.2"), false);
-        assert_eq!(is_number_string("2.2.3"), false);
-        assert_eq!(is_number_string("3.3.4"), false);
-        assert_eq!(is_number_string("4.4.5"), false);
-        assert_eq!(is_number_string("5.5.6"), false);
-        assert_eq!(is_number_string("6.6.7"), false);
-        assert_eq!(is_number_string("7.7.8"), false);
-        assert_eq!(is_number_string("8.8.9"), false);
-        assert_eq!(is_number_string("9.9.0"), false);
-        assert_eq!(is_number_string("0.0.1"), false);
-        assert_eq!(is_number_string("1e"), false);
-        assert_eq!(is_number_string("e3"), false);
-        assert_eq!(is_number_string("e"), false);
-        assert_eq!(is_number_string("1e2e"), false);
-        assert_eq!(is_number_string("5.8e10.1"), false);
-        assert_eq!(is_number_string("_100_1"), false);
-        assert_eq!(is_number_string("100_"), false);
-        assert_eq!(is_number_string("1_00_"), false);
-        assert_eq!(is_number_string("_"), false);
-        assert_eq!(is_number_string("0b102"), false);
-        assert_eq!(is_number_string("0x1G"), false);
-        assert_eq!(is_number_string("1o108"), false);
-        assert_eq!(is_number_string("0xxx"), false);
+        assert!(!is_number_string("1a"));
+        assert!(!is_number_string("2b"));
+        assert!(!is_number_string("3c"));
+        assert!(!is_number_string("4d"));
+        assert!(!is_number_string("5e"));
+        assert!(!is_number_string("6f"));
+        assert!(!is_number_string("7g"));
+        assert!(!is_number_string("8h"));
+        assert!(!is_number_string("9i"));
+        assert!(!is_number_string("0j"));
+        assert!(!is_number_string("100a200b300c400d500"));
+        assert!(!is_number_string("u32"));
+        assert!(!is_number_string("i8"));
+        assert!(!is_number_string("f64"));
+        assert!(!is_number_string("1.1.2"));
+        assert!(!is_number_string("2.2.3"));
+        assert!(!is_number_string("3.3.4"));
+        assert!(!is_number_string("4.4.5"));
+        assert!(!is_number_string("5.5.6"));
+        assert!(!is_number_string("6.6.7"));
+        assert!(!is_number_string("7.7.8"));
+        assert!(!is_number_string("8.8.9"));
+        assert!(!is_number_string("9.9.0"));
+        assert!(!is_number_string("0.0.1"));
+        assert!(!is_number_string("1e"));
+        assert!(!is_number_string("e3"));
+        assert!(!is_number_string("e"));
+        assert!(!is_number_string("1e2e"));
+        assert!(!is_number_string("5.8e10.1"));
+        assert!(!is_number_string("_100_1"));
+        assert!(!is_number_string("100_"));
+        assert!(!is_number_string("1_00_"));
+        assert!(!is_number_string("_"));
+        assert!(!is_number_string("0b102"));
+        assert!(!is_number_string("0x1G"));
+        assert!(!is_number_string("1o108"));
+        assert!(!is_number_string("0xxx"));
     }
 }
@@ -1,7 +1,9 @@
-use crate::editor::{Annotation, FileType, Line};
-use crate::prelude::{LineIdx, Location};
+use super::buffer::LineChange;
+use crate::editor::{Annotation, AnnotationType, FileType, Line};
+use crate::prelude::{ByteIdx, LineIdx, Location};
 use rust_syntax_highlighter::RustSyntaxHighlighter;
 use search_result_highlighter::SearchResultHighlighter;
+use std::ops::Range;
 mod rust_syntax_highlighter;
 mod search_result_highlighter;
 use syntax_highlighter::SyntaxHighlighter;
@@ -12,29 +14,107 @@ mod is_number_string;
 fn create_syntax_highlighter(file_type: FileType) -> Option<Box<dyn SyntaxHighlighter>> {
     match file_type {
         FileType::Rust => Some(Box::<RustSyntaxHighlighter>::default()),
-        FileType::Text => None,
+        FileType::Shell | FileType::Toml | FileType::Text => None,
     }
 }
 
+/// Keeps the syntax highlighting of a buffer between renders, so that only the changed lines
+/// and the ones after them whose starting state changed have to be highlighted again.
 #[derive(Default)]
-pub struct Highlighter<'a> {
+pub struct SyntaxCache {
+    file_type: Option<FileType>,
     syntax_highlighter: Option<Box<dyn SyntaxHighlighter>>,
+}
+
+impl SyntaxCache {
+    /// Starts over if the file type changed, otherwise applies the changes of the buffer.
+    pub fn update(&mut self, file_type: FileType, changes: &[LineChange]) {
+        if self.file_type != Some(file_type) {
+            self.file_type = Some(file_type);
+            self.syntax_highlighter = create_syntax_highlighter(file_type);
+            return;
+        }
+        if let Some(syntax_highlighter) = &mut self.syntax_highlighter {
+            for &change in changes {
+                syntax_highlighter.apply_change(change);
+            }
+        }
+    }
+}
+
+// struct_field_names: the fields are named after the highlighters they hold.
+#[allow(clippy::struct_field_names)]
+#[derive(Default)]
+pub struct Highlighter<'a> {
+    syntax_highlighter: Option<&'a mut Box<dyn SyntaxHighlighter>>,
     search_result_highlighter: Option<SearchResultHighlighter<'a>>,
+    matching_brackets: Vec<(LineIdx, ByteIdx)>,
+    selection: Vec<(LineIdx, Range<ByteIdx>)>,
+    highlighted_line_count: LineIdx,
 }
 
 impl<'a> Highlighter<'a> {
     pub fn new(
         matched_word: Option<&'a str>,
         selected_match: Option<Location>,
-        file_type: FileType,
+        syntax_cache: &'a mut SyntaxCache,
     ) -> Self {
         let search_result_highlighter = matched_word
             .map(|matched_word| SearchResultHighlighter::new(matched_word, selected_match));
         Self {
-            syntax_highlighter: create_syntax_highlighter(file_type),
+            syntax_highlighter: syntax_cache.syntax_highlighter.as_mut(),
             search_result_highlighter,
+            matching_brackets: Vec::new(),
+            selection: Vec::new(),
+            highlighted_line_count: 0,
+        }
+    }
+    /// Returns the first line to highlight so that `line_idx` gets highlighted correctly:
+    /// syntax highlighting carries its state from line to line, so it has to start at the top,
+    /// though lines whose cached annotations are still valid are merely skipped over.
+    pub fn first_line_for(&self, line_idx: LineIdx) -> LineIdx {
+        if self.syntax_highlighter.is_some() {
+            0
+        } else {
+            line_idx
         }
     }
+    pub fn highlighted_line_count(&self) -> LineIdx {
+        self.highlighted_line_count
+    }
+    /// Whether the given line can be skipped because its cached annotations are still valid.
+    /// Search results are not taken into account, as they are only highlighted where visible.
+    pub fn is_up_to_date(&self, line_idx: LineIdx) -> bool {
+        self.syntax_highlighter
+            .as_ref()
+            .is_none_or(|syntax_highlighter| syntax_highlighter.is_cached(line_idx))
+    }
+    pub fn skip(&mut self, line_idx: LineIdx) {
+        self.highlighted_line_count = line_idx.saturating_add(1);
+    }
+    /// Returns false if the given byte is part of a string, char or comment.
+    pub fn is_code(&self, line_idx: LineIdx, byte_idx: ByteIdx) -> bool {
+        self.syntax_highlighter
+            .as_ref()
+            .and_then(|syntax_highlighter| syntax_highlighter.get_annotations(line_idx))
+            .is_none_or(|annotations| {
+                !annotations.iter().any(|annotation| {
+                    matches!(
+                        annotation.annotation_type,
+                        AnnotationType::String | AnnotationType::Char | AnnotationType::Comment
+                    ) && annotation.start_byte_idx <= byte_idx
+                        && byte_idx < annotation.end_byte_idx
+                })
+            })
+    }
+    /// Marks the single-byte brackets at the given positions as a matching pair.
+    pub fn highlight_matching_brackets(&mut self, positions: [(LineIdx, ByteIdx); 2]) {
+        self.matching_brackets = positions.to_vec();
+    }
+    /// Marks the given byte ranges of each line as selected.
+    pub fn highlight_selection(&mut self, ranges: Vec<(LineIdx, Range<ByteIdx>)>) {
+        self.selection = ranges;
+    }
     pub fn get_annotations(&self, line_idx: LineIdx) -> Vec<Annotation> {
         let mut result = Vec::new();
         if let Some(syntax_highlighter) = &self.syntax_highlighter {
@@ -47,12 +127,36 @@ impl<'a> Highlighter<'a> {
                 result.extend(annotations.iter().copied());
             }
         }
+        for &(bracket_line_idx, start_byte_idx) in &self.matching_brackets {
+            if bracket_line_idx == line_idx {
+                result.push(Annotation {
+                    annotation_type: AnnotationType::MatchingBracket,
+                    start_byte_idx,
+                    end_byte_idx: start_byte_idx.saturating_add(1),
+                });
+            }
+        }
+        for (selected_line_idx, range) in &self.selection {
+            if *selected_line_idx == line_idx {
+                result.push(Annotation {
+                    annotation_type: AnnotationType::Selection,
+                    start_byte_idx: range.start,
+                    end_byte_idx: range.end,
+                });
+            }
+        }
         result
     }
     pub fn highlight(&mut self, line_idx: LineIdx, line: &Line) {
         if let Some(syntax_highlighter) = &mut self.syntax_highlighter {
-            syntax_highlighter.highlight(line_idx, line);
+            if !syntax_highlighter.is_cached(line_idx) {
+                syntax_highlighter.highlight(line_idx, line);
+            }
         }
+        self.highlighted_line_count = line_idx.saturating_add(1);
+    }
+    /// Marks the matches of the search query in the given line.
+    pub fn highlight_search_results(&mut self, line_idx: LineIdx, line: &Line) {
         if let Some(search_result_highlighter) = &mut self.search_result_highlighter {
             search_result_highlighter.highlight(line_idx, line);
         }
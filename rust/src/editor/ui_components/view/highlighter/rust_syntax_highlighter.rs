@@ -1,17 +1,47 @@
 use super::is_number_string;
-use super::SyntaxHighlighter;
+use super::{LineChange, SyntaxHighlighter};
 use crate::editor::{Annotation, AnnotationType, Line};
 use crate::prelude::LineIdx;
 use unicode_segmentation::UnicodeSegmentation;
 
+// the state carried over from one line to the next
+#[derive(Default, Copy, Clone, Eq, PartialEq)]
+struct State {
+    ml_comment_balance: usize,
+    in_string: bool,
+}
+
+struct HighlightedLine {
+    start_state: State,
+    end_state: State,
+    annotations: Vec<Annotation>,
+}
+
 #[derive(Default)]
 pub struct RustSyntaxHighlighter {
-    highlights: Vec<Vec<Annotation>>,
+    // by line index, None for lines which changed since they were highlighted
+    lines: Vec<Option<HighlightedLine>>,
     ml_comment_balance: usize,
     in_string: bool,
 }
 
 impl RustSyntaxHighlighter {
+    fn state(&self) -> State {
+        State {
+            ml_comment_balance: self.ml_comment_balance,
+            in_string: self.in_string,
+        }
+    }
+    fn state_before(&self, line_idx: LineIdx) -> Option<State> {
+        match line_idx.checked_sub(1) {
+            None => Some(State::default()),
+            Some(previous) => self
+                .lines
+                .get(previous)
+                .and_then(Option::as_ref)
+                .map(|line| line.end_state),
+        }
+    }
     fn annotate_ml_comment(&mut self, string: &str) -> Option<Annotation> {
         let mut chars = string.char_indices().peekable();
         while let Some((_, c)) = chars.next() {
@@ -94,7 +124,9 @@ impl RustSyntaxHighlighter {
 }
 impl SyntaxHighlighter for RustSyntaxHighlighter {
     fn highlight(&mut self, line_idx: LineIdx, line: &Line) {
-        debug_assert_eq!(line_idx, self.highlights.len());
+        let start_state = self.state_before(line_idx).unwrap_or_default();
+        self.ml_comment_balance = start_state.ml_comment_balance;
+        self.in_string = start_state.in_string;
         let mut result = Vec::new();
         let mut iterator = line.split_word_bound_indices().peekable();
         // handle dangling multi-line annotations
@@ -132,10 +164,49 @@ impl SyntaxHighlighter for RustSyntaxHighlighter {
                 }
             }
         }
-        self.highlights.push(result);
+        if line_idx >= self.lines.len() {
+            self.lines.resize_with(line_idx.saturating_add(1), || None);
+        }
+        let end_state = self.state();
+        if let Some(entry) = self.lines.get_mut(line_idx) {
+            *entry = Some(HighlightedLine {
+                start_state,
+                end_state,
+                annotations: result,
+            });
+        }
     }
     fn get_annotations(&self, line_idx: LineIdx) -> Option<&Vec<Annotation>> {
-        self.highlights.get(line_idx)
+        self.lines
+            .get(line_idx)
+            .and_then(Option::as_ref)
+            .map(|line| &line.annotations)
+    }
+    fn is_cached(&self, line_idx: LineIdx) -> bool {
+        let Some(Some(line)) = self.lines.get(line_idx) else {
+            return false;
+        };
+        self.state_before(line_idx) == Some(line.start_state)
+    }
+    fn apply_change(&mut self, change: LineChange) {
+        match change {
+            LineChange::Edited(line_idx) => {
+                if let Some(entry) = self.lines.get_mut(line_idx) {
+                    *entry = None;
+                }
+            }
+            LineChange::Inserted(line_idx) => {
+                if line_idx <= self.lines.len() {
+                    self.lines.insert(line_idx, None);
+                }
+            }
+            LineChange::Removed(line_idx) => {
+                if line_idx < self.lines.len() {
+                    self.lines.remove(line_idx);
+                }
+            }
+            LineChange::Reset => self.lines.clear(),
+        }
     }
 }
 
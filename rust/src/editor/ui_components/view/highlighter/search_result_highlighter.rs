@@ -51,7 +51,7 @@ impl<'a> SearchResultHighlighter<'a> {
     }
 }
 
-impl<'a> SyntaxHighlighter for SearchResultHighlighter<'a> {
+impl SyntaxHighlighter for SearchResultHighlighter<'_> {
     fn highlight(&mut self, line_idx: LineIdx, line: &Line) {
         let mut result = Vec::new();
         self.highlight_matched_words(line, &mut result);
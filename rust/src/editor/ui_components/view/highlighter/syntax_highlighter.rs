@@ -1,7 +1,15 @@
+use super::LineChange;
 use crate::editor::{Annotation, Line};
 use crate::prelude::LineIdx;
 
 pub trait SyntaxHighlighter {
     fn highlight(&mut self, line_idx: LineIdx, line: &Line);
     fn get_annotations(&self, line_idx: LineIdx) -> Option<&Vec<Annotation>>;
+    /// Whether the annotations of the given line from an earlier run are still valid,
+    /// given that all lines before it are up to date.
+    fn is_cached(&self, _line_idx: LineIdx) -> bool {
+        false
+    }
+    /// Forgets or moves the cached annotations affected by a change of the buffer.
+    fn apply_change(&mut self, _change: LineChange) {}
 }
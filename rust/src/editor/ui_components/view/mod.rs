@@ -1,18 +1,19 @@
 use crate::prelude::*;
 use buffer::Buffer;
-use std::cmp::min;
+use std::cmp::{min, Ordering};
 use std::io::Error;
+use std::ops::Range;
 mod buffer;
 use super::UIComponent;
 use crate::editor::{
     command::{Edit, Move},
     terminal::Terminal,
-    Line,
+    FileType, Line, Settings, TextEncoding, Written,
 };
 use search_direction::SearchDirection;
 use search_info::SearchInfo;
 mod search_direction;
-use highlighter::Highlighter;
+use highlighter::{Highlighter, SyntaxCache};
 mod highlighter;
 mod search_info;
 
@@ -25,37 +26,150 @@ pub struct View {
     size: Size,
     pub text_location: Location,
     scroll_offset: Position,
+    // the scroll offset of the last draw, to scroll the rows already on the terminal
+    drawn_scroll_offset: Option<Position>,
     search_info: Option<SearchInfo>,
+    syntax_cache: SyntaxCache,
+    settings: Settings,
+    // number of auto-inserted closing characters right of the caret,
+    // which are skipped over when typed
+    auto_closed_count: usize,
+    // where the selection started, which spans from here to the caret
+    selection_anchor: Option<Location>,
 }
 
 impl View {
     pub fn handle_edit_command(&mut self, edit_command: Edit) {
+        if let Some(selection) = self.selection() {
+            match edit_command {
+                Edit::ToggleComment => {
+                    let lines = Self::selected_lines(&selection);
+                    self.toggle_comment(lines);
+                    self.clear_selection();
+                    return;
+                }
+                Edit::Delete | Edit::DeleteBackward => {
+                    self.remove_selection(selection);
+                    return;
+                }
+                // typing replaces the selection
+                Edit::Insert(_) | Edit::InsertNewLine => self.remove_selection(selection),
+                Edit::InsertLineBelow | Edit::InsertTab | Edit::Dedent | Edit::ToggleLineEnding => {
+                }
+            }
+        }
+        self.clear_selection();
         match edit_command {
             Edit::Insert(c) => self.insert(c),
+            Edit::InsertTab => self.insert_tab(),
+            Edit::Dedent => self.dedent(),
+            Edit::ToggleComment => {
+                let line_idx = self.text_location.line_idx;
+                self.toggle_comment(line_idx..line_idx.saturating_add(1));
+            }
+            Edit::ToggleLineEnding => self.buffer.toggle_line_ending(),
             Edit::InsertNewLine => self.enter(),
+            Edit::InsertLineBelow => {
+                self.move_to_end_of_line();
+                self.enter();
+            }
             Edit::DeleteBackward => self.backspace(),
             Edit::Delete => self.delete(),
         }
     }
-    pub fn save(&mut self) -> Result<(), Error> {
+    pub fn save(&mut self) -> Result<Written, Error> {
         let result = self.buffer.save();
         self.set_needs_redraw(true);
         result
     }
-    pub fn save_as(&mut self, filename: &str) -> Result<(), Error> {
+    pub fn save_as(&mut self, filename: &str) -> Result<Written, Error> {
         let result = self.buffer.save_as(filename);
         self.set_needs_redraw(true);
         result
     }
+    fn file_type(&self) -> FileType {
+        self.buffer.file_info.get_file_type().unwrap_or_default()
+    }
+    fn char_before_caret(&self) -> Option<char> {
+        let grapheme_idx = self.text_location.grapheme_idx.checked_sub(1)?;
+        self.buffer.char_at(Location {
+            grapheme_idx,
+            line_idx: self.text_location.line_idx,
+        })
+    }
     pub fn insert(&mut self, c: char) {
+        let file_type = self.file_type();
+        let next = self.buffer.char_at(self.text_location);
+        // skip over the closing character which was inserted automatically
+        if self.auto_closed_count > 0 && next == Some(c) && file_type.is_auto_pair_close(c) {
+            self.auto_closed_count = self.auto_closed_count.saturating_sub(1);
+            self.move_text_location(Move::Right);
+            return;
+        }
+        let line = self.buffer.line(self.text_location.line_idx);
+        let before = line.as_deref().map_or("", |line| {
+            let byte_idx = line.grapheme_idx_to_byte_idx(self.text_location.grapheme_idx);
+            line.get(..byte_idx).unwrap_or_default()
+        });
+        let closing_pair = file_type.closing_pair(c, before, next);
         if self.buffer.insert_char(c, self.text_location) {
-            self.handle_move_command(Move::Right);
+            self.move_text_location(Move::Right);
+            if let Some(close) = closing_pair {
+                if self.buffer.insert_char(close, self.text_location) {
+                    self.auto_closed_count = self.auto_closed_count.saturating_add(1);
+                }
+            }
             self.needs_redraw = true;
         }
     }
+    pub fn insert_tab(&mut self) {
+        if !self.settings.expand_tabs {
+            self.insert('\t');
+            return;
+        }
+        // fill up with spaces until the next tab stop
+        let tab_width = self.settings.tab_width;
+        let col = self.text_location_to_position().col;
+        let offset = col.checked_rem(tab_width).unwrap_or(0);
+        for _ in 0..tab_width.saturating_sub(offset) {
+            self.insert(' ');
+        }
+    }
+    pub fn dedent(&mut self) {
+        let removed = self.buffer.dedent(self.text_location.line_idx);
+        if removed > 0 {
+            self.text_location.grapheme_idx =
+                self.text_location.grapheme_idx.saturating_sub(removed);
+            self.scroll_into_view();
+            self.needs_redraw = true;
+        }
+    }
+    /// Toggles the line comments of the given lines.
+    pub fn toggle_comment(&mut self, lines: Range<LineIdx>) {
+        let Some(token) = self.file_type().line_comment() else {
+            return;
+        };
+        let line_idx = self.text_location.line_idx;
+        let before = self.buffer.grapheme_count(line_idx);
+        if !self.buffer.toggle_comment(lines, token) {
+            return;
+        }
+        // keep the caret on the same character
+        let after = self.buffer.grapheme_count(line_idx);
+        let grapheme_idx = self.text_location.grapheme_idx;
+        self.text_location.grapheme_idx = if after >= before {
+            grapheme_idx.saturating_add(after.saturating_sub(before))
+        } else {
+            grapheme_idx.saturating_sub(before.saturating_sub(after))
+        };
+        self.snap_to_valid_x();
+        self.scroll_into_view();
+        self.needs_redraw = true;
+    }
     pub fn enter(&mut self) {
         if self.buffer.insert_newline(self.text_location) {
-            self.handle_move_command(Move::Right);
+            self.auto_closed_count = 0;
+            self.move_text_location(Move::Right);
             self.needs_redraw = true;
         }
     }
@@ -68,15 +182,117 @@ impl View {
         if grapheme_idx == 0 && line_idx == 0 {
             return;
         }
-        self.handle_move_command(Move::Left);
+        // delete both characters of an empty pair, like `(|)`
+        let is_empty_pair = self
+            .char_before_caret()
+            .zip(self.buffer.char_at(self.text_location))
+            .is_some_and(|(open, close)| self.file_type().is_auto_pair(open, close));
+        self.move_text_location(Move::Left);
         self.delete();
+        if is_empty_pair {
+            self.delete();
+            self.auto_closed_count = self.auto_closed_count.saturating_sub(1);
+        }
     }
     pub fn delete(&mut self) {
         if self.buffer.remove_char(self.text_location) {
             self.needs_redraw = true;
         }
     }
+    /// Returns the selected text from its start to its end, if any is selected.
+    fn selection(&self) -> Option<Range<Location>> {
+        let anchor = self.selection_anchor?;
+        let caret = self.text_location;
+        match anchor.cmp(&caret) {
+            Ordering::Less => Some(anchor..caret),
+            Ordering::Greater => Some(caret..anchor),
+            Ordering::Equal => None,
+        }
+    }
+    /// Returns the lines touched by the selection,
+    /// without the last one if the selection ends at its very start.
+    fn selected_lines(selection: &Range<Location>) -> Range<LineIdx> {
+        let Range { start, end } = selection;
+        let end_line_idx = if end.grapheme_idx == 0 && end.line_idx > start.line_idx {
+            end.line_idx
+        } else {
+            end.line_idx.saturating_add(1)
+        };
+        start.line_idx..end_line_idx
+    }
+    fn clear_selection(&mut self) {
+        if self.selection_anchor.take().is_some() {
+            self.needs_redraw = true;
+        }
+    }
+    fn remove_selection(&mut self, selection: Range<Location>) {
+        self.text_location = selection.start;
+        self.buffer.remove_range(selection);
+        self.clear_selection();
+        self.auto_closed_count = 0;
+        self.scroll_into_view();
+        self.needs_redraw = true;
+    }
+    /// Places the caret at the given position of the view,
+    /// and starts a selection there which `drag` extends.
+    pub fn click(&mut self, position: Position) {
+        self.auto_closed_count = 0;
+        self.text_location = self.location_at(position);
+        self.selection_anchor = Some(self.text_location);
+        self.scroll_into_view();
+        self.needs_redraw = true;
+    }
+    /// Selects up to the given position, from where the last click was.
+    /// Positions beyond the view scroll it.
+    pub fn drag(&mut self, position: Position) {
+        if self.selection_anchor.is_none() {
+            return;
+        }
+        self.text_location = self.location_at(position);
+        self.scroll_into_view();
+        self.needs_redraw = true;
+    }
+    /// Maps a position of the view to the location of the grapheme shown there,
+    /// or to the end of the line if there is none.
+    fn location_at(&self, position: Position) -> Location {
+        let line_idx = min(
+            position.row.saturating_add(self.scroll_offset.row),
+            self.buffer.height(),
+        );
+        let col = position.col.saturating_add(self.scroll_offset.col);
+        Location {
+            line_idx,
+            grapheme_idx: self.buffer.grapheme_idx_at(line_idx, col),
+        }
+    }
+    /// Scrolls the view up by the given number of lines,
+    /// taking the caret along as far as needed to keep it visible.
+    pub fn scroll_up(&mut self, lines: usize) {
+        self.scroll_offset.row = self.scroll_offset.row.saturating_sub(lines);
+        self.keep_caret_in_view();
+    }
+    /// Scrolls the view down, but no further than showing the end of the buffer at the bottom.
+    pub fn scroll_down(&mut self, lines: usize) {
+        let max_row = self
+            .buffer
+            .height()
+            .saturating_sub(self.size.height.saturating_sub(1));
+        if self.scroll_offset.row < max_row {
+            self.scroll_offset.row = min(self.scroll_offset.row.saturating_add(lines), max_row);
+        }
+        self.keep_caret_in_view();
+    }
+    fn keep_caret_in_view(&mut self) {
+        let top = self.scroll_offset.row;
+        let bottom = top.saturating_add(self.size.height.saturating_sub(1));
+        self.text_location.line_idx = self.text_location.line_idx.clamp(top, bottom);
+        self.snap_to_valid_y();
+        self.snap_to_valid_x();
+        self.scroll_horizontally(self.text_location_to_position().col);
+        self.needs_redraw = true;
+    }
     pub fn enter_search(&mut self) {
+        self.clear_selection();
         self.search_info = Some(SearchInfo {
             prev_location: self.text_location,
             prev_scroll_offset: self.scroll_offset,
@@ -138,12 +354,66 @@ impl View {
     pub fn search_prev(&mut self) {
         self.search_in_direction(self.text_location, SearchDirection::Backward);
     }
+    pub fn set_settings(&mut self, settings: Settings) {
+        self.settings = settings;
+        self.buffer.set_settings(settings);
+        self.needs_redraw = true;
+    }
     pub fn load(&mut self, filename: &str) -> Result<(), Error> {
-        let buffer = Buffer::load(filename)?;
+        let mut buffer = Buffer::load(filename)?;
+        buffer.set_settings(self.settings);
+        self.buffer = buffer;
+        // start over at the top of the new contents
+        self.text_location = Location::default();
+        self.scroll_offset = Position::default();
+        self.auto_closed_count = 0;
+        self.needs_redraw = true;
+        Ok(())
+    }
+    /// Replaces the buffer with an unnamed one holding `bytes`, e.g. read from stdin.
+    pub fn load_unnamed(&mut self, bytes: &[u8]) {
+        let (contents, encoding, _) = TextEncoding::decode(bytes);
+        let mut buffer = Buffer::default();
+        buffer.set_settings(self.settings);
+        buffer.set_contents(&contents);
+        buffer.file_info.set_encoding(encoding);
         self.buffer = buffer;
         self.needs_redraw = true;
+    }
+    /// Reads the file of the buffer again, discarding all unsaved changes.
+    /// The caret stays on the same line and the scroll offset is kept, as far as possible.
+    pub fn reload(&mut self) -> Result<(), Error> {
+        let filename = self
+            .buffer
+            .file_info
+            .get_path()
+            .map(|path| path.to_string_lossy().to_string())
+            .ok_or_else(|| Error::other("No file path"))?;
+        let mut buffer = Buffer::load(&filename)?;
+        buffer.set_settings(self.settings);
+        self.buffer = buffer;
+        self.auto_closed_count = 0;
+        self.selection_anchor = None;
+        self.snap_to_valid_y();
+        self.snap_to_valid_x();
+        // don't scroll past the end of a file which got shorter
+        let last_page = self.buffer.height().saturating_sub(self.size.height);
+        self.scroll_offset.row = min(self.scroll_offset.row, last_page);
+        self.scroll_into_view();
+        self.needs_redraw = true;
         Ok(())
     }
+    /// Replaces the contents of the buffer, keeping the file it belongs to.
+    /// The buffer counts as modified afterwards.
+    pub fn recover(&mut self, contents: &str) {
+        self.buffer.set_contents(contents);
+        self.buffer.dirty = self.buffer.dirty.saturating_add(1);
+        self.selection_anchor = None;
+        self.snap_to_valid_y();
+        self.snap_to_valid_x();
+        self.scroll_into_view();
+        self.needs_redraw = true;
+    }
     fn draw_welcome_message(&self) {
         let messages = vec![
             "A long time ago in a galaxy far, far away...".to_string(),
@@ -156,7 +426,11 @@ impl View {
 
         // minus 1 for FILLCHAR_EOB
         let display_width = self.size.width.saturating_sub(1);
-        let mut row = self.size.height.div_ceil(3);
+        // a third down, as far as the messages fit
+        let mut row = min(
+            self.size.height.div_ceil(3),
+            self.size.height.saturating_sub(messages.len()),
+        );
         for mut message in messages {
             if display_width < message.len() {
                 Self::render_line(row, FILLCHAR_EOB);
@@ -168,6 +442,23 @@ impl View {
             row = row.saturating_add(1);
         }
     }
+    /// Lets the terminal move the rows which stay visible after scrolling vertically,
+    /// so that only the rows scrolled into view have to be sent.
+    fn scroll_drawn_rows(&mut self, rows: Range<RowIdx>) {
+        let drawn = self.drawn_scroll_offset.replace(self.scroll_offset);
+        let Some(drawn) = drawn.filter(|drawn| drawn.col == self.scroll_offset.col) else {
+            return;
+        };
+        if !self.settings.scroll_regions || drawn.row == self.scroll_offset.row {
+            return;
+        }
+        let result = if self.scroll_offset.row > drawn.row {
+            Terminal::scroll_rows_up(rows, self.scroll_offset.row.saturating_sub(drawn.row))
+        } else {
+            Terminal::scroll_rows_down(rows, drawn.row.saturating_sub(self.scroll_offset.row))
+        };
+        debug_assert!(result.is_ok(), "Failed to scroll rows");
+    }
     fn render_line(row: RowIdx, line_text: &str) {
         let result = Terminal::print_row(row, line_text);
         debug_assert!(result.is_ok(), "Failed to render line");
@@ -192,6 +483,12 @@ impl View {
     // }
 
     pub fn handle_move_command(&mut self, move_command: Move) {
+        // moving around forgets about the auto-inserted closing characters
+        self.auto_closed_count = 0;
+        self.clear_selection();
+        self.move_text_location(move_command);
+    }
+    fn move_text_location(&mut self, move_command: Move) {
         // This match moves the position, but does not check for all boundaries.
         // The final boundary checking happens after the match statement.
         match move_command {
@@ -203,7 +500,7 @@ impl View {
             Move::EndOfLine => self.move_to_end_of_line(),
             Move::PageUp => self.move_up(self.size.height.saturating_sub(1)),
             Move::PageDown => self.move_down(self.size.height.saturating_sub(1)),
-        };
+        }
 
         self.scroll_into_view();
     }
@@ -297,24 +594,37 @@ impl UIComponent for View {
         self.scroll_into_view();
     }
     fn draw(&mut self, origin_row: RowIdx) -> Result<(), Error> {
-        let _ = Terminal::move_caret_to(Position::default());
         let Size { width, height } = self.size;
         let top = self.scroll_offset.row.saturating_sub(origin_row);
         let left = self.scroll_offset.col;
         let right = left.saturating_add(width);
         let end_y = origin_row.saturating_add(height);
+        self.scroll_drawn_rows(origin_row..end_y);
         let query = self
             .search_info
             .as_ref()
             .and_then(|search_info| search_info.query.as_deref());
         let selected_match = query.is_some().then_some(self.text_location);
-        let file_type = self.buffer.file_info.get_file_type().unwrap_or_default();
-        let mut highlighter = Highlighter::new(query, selected_match, file_type);
-        // highlight from the top to the end of the visible area,
-        // to ensure all annotations are up to date
-        for current_row in 0..end_y.saturating_add(top) {
+        let selection = self.selection();
+        let file_type = self.file_type();
+        self.syntax_cache
+            .update(file_type, &self.buffer.take_line_changes());
+        let mut highlighter = Highlighter::new(query, selected_match, &mut self.syntax_cache);
+        // highlight up to the end of the visible area, starting from the top
+        // if needed to ensure all annotations are up to date
+        for current_row in highlighter.first_line_for(top)..end_y.saturating_add(top) {
             self.buffer.highlight(current_row, &mut highlighter);
         }
+        let lines = origin_row.saturating_add(top)..end_y.saturating_add(top);
+        // this may highlight further lines to find a partner below the visible area
+        self.buffer
+            .highlight_matching_bracket(self.text_location, lines.clone(), &mut highlighter);
+        self.buffer
+            .highlight_search_results(lines.clone(), &mut highlighter);
+        if let Some(selection) = selection {
+            self.buffer
+                .highlight_selection(&selection, lines, &mut highlighter);
+        }
         for current_row in origin_row..end_y {
             let line_idx = current_row.saturating_add(top);
             if let Some(annotated_string) =
@@ -332,3 +642,58 @@ impl UIComponent for View {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn numbered_lines(count: usize) -> String {
+        let lines: Vec<String> = (1..=count).map(|n| format!("line {n}")).collect();
+        format!("{}\n", lines.join("\n"))
+    }
+
+    #[test]
+    fn test_reload_keeps_caret_and_scroll_offset() {
+        let path = std::env::temp_dir().join(format!("hecto-reload-{}.txt", std::process::id()));
+        fs::write(&path, numbered_lines(100)).unwrap();
+        let mut view = View::default();
+        view.set_size(Size {
+            width: 40,
+            height: 10,
+        });
+        view.load(path.to_str().unwrap()).unwrap();
+        view.text_location = Location {
+            grapheme_idx: 7,
+            line_idx: 60,
+        };
+        view.scroll_into_view();
+        let scroll_offset = view.scroll_offset;
+        assert_eq!(scroll_offset.row, 51);
+
+        // shorter lines: the caret moves to the end of its line, and nothing scrolls
+        fs::write(&path, "x\n".repeat(80)).unwrap();
+        view.reload().unwrap();
+        assert_eq!(
+            view.text_location,
+            Location {
+                grapheme_idx: 1,
+                line_idx: 60,
+            }
+        );
+        assert_eq!(view.scroll_offset, scroll_offset);
+
+        // fewer lines: the caret moves up to the end of the file, and into view
+        fs::write(&path, numbered_lines(30)).unwrap();
+        view.reload().unwrap();
+        assert_eq!(
+            view.text_location,
+            Location {
+                grapheme_idx: 0,
+                line_idx: 30,
+            }
+        );
+        assert_eq!(view.scroll_offset.row, 21);
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -1,8 +1,22 @@
 use crate::prelude::{GraphemeIdx, LineIdx};
+use std::cmp::Ordering;
 
-#[derive(Copy, Clone, Default)]
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
 pub struct Location {
     // the position of the document
     pub grapheme_idx: GraphemeIdx,
     pub line_idx: LineIdx,
 }
+
+// locations are ordered by line first, then by the grapheme within the line
+impl Ord for Location {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.line_idx, self.grapheme_idx).cmp(&(other.line_idx, other.grapheme_idx))
+    }
+}
+
+impl PartialOrd for Location {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
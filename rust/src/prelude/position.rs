@@ -1,6 +1,6 @@
 use super::{ColIdx, RowIdx};
 
-#[derive(Copy, Clone, Default)]
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
 pub struct Position {
     // the position of the screen
     pub col: ColIdx,